@@ -1,5 +1,14 @@
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
 fn main() {
     linker_be_nice();
+    generate_animations();
     println!("cargo:rustc-link-arg=-Tdefmt.x");
     // make sure linkall.x is the last linker script (otherwise might cause problems with flip-link)
     println!("cargo:rustc-link-arg=-Tlinkall.x");
@@ -69,3 +78,514 @@ fn linker_be_nice() {
         std::env::current_exe().unwrap().display()
     );
 }
+
+// Animations
+//
+// Every `.owl` file in the animations directory becomes a static `Animation` table and a
+// variant of `AnimationType`. A file holds directives and one keyframe per line:
+//
+//     # comment
+//     name = pick_up            name used over MQTT, defaults to the file name
+//     easing = cubic-in-out     easing for the keyframes that follow, defaults to linear
+//     t=250ms beak=1000 cubic-in-out neck=+100 wings=0 audio=bubo_yap_6
+//     t=500ms eyes=#ff0000 chest=#200000 cubic-in-out
+//
+// Positions are absolute, or relative to the servo's default position when they start with
+// `+` or `-`. LED groups fade between colors the same way. `default` moves every servo to its
+// default position and leaves the LEDs as they are. A file can instead point at a composed
+// `Script` with `script = panic::SCRIPT`.
+
+const ANIMATIONS_DIR: &str = "src/bin/modules/servo/animations";
+const TRACKS_DIR: &str = "src/bin/modules/audio/tracks";
+const KEYFRAME_MILLIS: u64 = 250;
+const SERVO_MAX: u16 = 1000;
+
+const SERVO_COUNT: usize = 4;
+
+// Servos, then LED groups
+const TRACK_FIELDS: [&str; 6] = [
+    "beak_servo",
+    "neck_servo",
+    "wing_right_servo",
+    "wing_left_servo",
+    "eye_leds",
+    "chest_leds",
+];
+
+const SERVO_DEFAULTS: [&str; 4] = [
+    "DEFAULT_BEAK_POSITION",
+    "DEFAULT_NECK_POSITION",
+    "DEFAULT_WING_POSITION",
+    "DEFAULT_WING_POSITION",
+];
+
+struct OwlError {
+    line: usize,
+    message: String,
+}
+
+#[derive(Default)]
+struct OwlFrame {
+    default: bool,
+    tracks: [Option<(String, &'static str)>; 6],
+    audio: Option<String>,
+}
+
+enum OwlSource {
+    Frames(Vec<Option<OwlFrame>>),
+    Script(String),
+}
+
+struct OwlAnimation {
+    name: String,
+    variant: String,
+    table: String,
+    source: OwlSource,
+}
+
+fn generate_animations() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let animations_dir = manifest_dir.join(ANIMATIONS_DIR);
+    let tracks_dir = manifest_dir.join(TRACKS_DIR);
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={ANIMATIONS_DIR}");
+    println!("cargo:rerun-if-changed={TRACKS_DIR}");
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(&animations_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "owl"))
+        .collect();
+    paths.sort();
+
+    let mut animations = Vec::new();
+    let mut failed = false;
+
+    for path in &paths {
+        let display_path = path.strip_prefix(&manifest_dir).unwrap_or(path).display();
+        let source = fs::read_to_string(path).unwrap();
+
+        match parse_owl(path, &source, &tracks_dir) {
+            Ok(animation) => animations.push(animation),
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("error: {}:{}: {}", display_path, error.line, error.message);
+                }
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("animations.rs");
+    fs::write(out_path, render_animations(&animations)).unwrap();
+}
+
+fn parse_owl(path: &Path, source: &str, tracks_dir: &Path) -> Result<OwlAnimation, Vec<OwlError>> {
+    let stem = path.file_stem().unwrap().to_str().unwrap();
+
+    let mut name = stem.to_string();
+    let mut script: Option<(String, usize)> = None;
+    let mut easing = "Linear";
+    let mut frames: Vec<Option<OwlFrame>> = Vec::new();
+    let mut errors = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = strip_comment(line).trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut error = |message: String| {
+            errors.push(OwlError {
+                line: line_number,
+                message,
+            })
+        };
+
+        if !line.starts_with("t=") {
+            let Some((key, value)) = line.split_once('=') else {
+                error(format!("expected a directive or keyframe, found `{line}`"));
+                continue;
+            };
+
+            match (key.trim(), value.trim()) {
+                ("name", value) if is_valid_name(value) => name = value.to_string(),
+                ("name", value) => error(format!(
+                    "name `{value}` may only use lowercase letters, digits, `_` and `-`"
+                )),
+                ("easing", value) => match parse_easing(value) {
+                    Some(value) => easing = value,
+                    None => error(format!("unknown easing `{value}`")),
+                },
+                ("script", value) => script = Some((value.to_string(), line_number)),
+                (key, _) => error(format!("unknown directive `{key}`")),
+            }
+            continue;
+        }
+
+        match parse_keyframe(line, easing, tracks_dir) {
+            Ok((millis, frame)) => {
+                if millis % KEYFRAME_MILLIS != 0 {
+                    error(format!(
+                        "t={millis}ms is not a multiple of {KEYFRAME_MILLIS}ms"
+                    ));
+                    continue;
+                }
+
+                let frame_index = (millis / KEYFRAME_MILLIS) as usize;
+                if frame_index < frames.len() {
+                    error(format!("t={millis}ms is not after the previous keyframe"));
+                    continue;
+                }
+
+                frames.resize_with(frame_index, || None);
+                frames.push(Some(frame));
+            }
+            Err(message) => error(message),
+        }
+    }
+
+    let source = match script {
+        Some((script, line)) if !frames.is_empty() => {
+            errors.push(OwlError {
+                line,
+                message: format!("`script = {script}` can't be combined with keyframes"),
+            });
+            OwlSource::Script(script)
+        }
+        Some((script, _)) => OwlSource::Script(script),
+        None if frames.is_empty() => {
+            errors.push(OwlError {
+                line: source.lines().count().max(1),
+                message: "animation has no keyframes".to_string(),
+            });
+            OwlSource::Frames(frames)
+        }
+        None => OwlSource::Frames(frames),
+    };
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(OwlAnimation {
+        name,
+        variant: to_camel_case(stem),
+        table: stem.to_uppercase(),
+        source,
+    })
+}
+
+/// Names end up in the generated code and in MQTT payloads.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.bytes().all(|byte| {
+            byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'_' || byte == b'-'
+        })
+}
+
+/// Comments start with a `#` at the start of the line or after a space, colors use it too.
+fn strip_comment(line: &str) -> &str {
+    let mut previous = ' ';
+    for (index, character) in line.char_indices() {
+        if character == '#' && previous.is_whitespace() {
+            return &line[..index];
+        }
+        previous = character;
+    }
+    line
+}
+
+fn parse_keyframe(
+    line: &str,
+    default_easing: &'static str,
+    tracks_dir: &Path,
+) -> Result<(u64, OwlFrame), String> {
+    let mut tokens = line.split_whitespace();
+    let time = tokens.next().unwrap().trim_start_matches("t=");
+    let millis = parse_time(time).ok_or_else(|| format!("invalid time `{time}`"))?;
+
+    let mut frame = OwlFrame::default();
+    // Tracks set by the previous token, an easing after them applies to all of them
+    let mut last_tracks: &[usize] = &[];
+
+    for token in tokens {
+        if token == "default" {
+            frame.default = true;
+            last_tracks = &[];
+            continue;
+        }
+
+        let Some((key, value)) = token.split_once('=') else {
+            let easing = parse_easing(token).ok_or_else(|| format!("unknown easing `{token}`"))?;
+            if last_tracks.is_empty() {
+                return Err(format!(
+                    "easing `{token}` doesn't follow a servo or LED group"
+                ));
+            }
+            for &track in last_tracks {
+                frame.tracks[track].as_mut().unwrap().1 = easing;
+            }
+            last_tracks = &[];
+            continue;
+        };
+
+        let tracks: &[usize] = match key {
+            "beak" => &[0],
+            "neck" => &[1],
+            "wing_r" => &[2],
+            "wing_l" => &[3],
+            "wings" => &[2, 3],
+            "eyes" => &[4],
+            "chest" => &[5],
+            "audio" => {
+                if !tracks_dir.join(format!("{value}.pcm")).exists() {
+                    return Err(format!("unknown track `{value}`"));
+                }
+                frame.audio = Some(to_camel_case(value));
+                last_tracks = &[];
+                continue;
+            }
+            _ => return Err(format!("unknown key `{key}`")),
+        };
+
+        for &track in tracks {
+            if frame.tracks[track].is_some() {
+                return Err(format!("`{}` is set more than once", TRACK_FIELDS[track]));
+            }
+            let value = match track {
+                track if track < SERVO_COUNT => parse_position(value, track)?,
+                _ => parse_color(value)?,
+            };
+            frame.tracks[track] = Some((value, default_easing));
+        }
+        last_tracks = tracks;
+    }
+
+    Ok((millis, frame))
+}
+
+fn parse_time(time: &str) -> Option<u64> {
+    if let Some(millis) = time.strip_suffix("ms") {
+        return millis.parse().ok();
+    }
+
+    let seconds: f64 = time.strip_suffix('s')?.parse().ok()?;
+    Some((seconds * 1000.0).round() as u64)
+}
+
+fn parse_position(value: &str, servo: usize) -> Result<String, String> {
+    let default = format!("crate::modules::servo::config::{}", SERVO_DEFAULTS[servo]);
+
+    if let Some(offset) = value.strip_prefix('+') {
+        let offset: u16 = offset
+            .parse()
+            .map_err(|_| format!("invalid offset `{value}`"))?;
+        return Ok(match offset {
+            0 => default,
+            _ => format!("{default} + {offset}"),
+        });
+    }
+
+    if let Some(offset) = value.strip_prefix('-') {
+        let offset: u16 = offset
+            .parse()
+            .map_err(|_| format!("invalid offset `{value}`"))?;
+        return Ok(match offset {
+            0 => default,
+            _ => format!("{default} - {offset}"),
+        });
+    }
+
+    match value.parse::<u16>() {
+        Ok(position) if position <= SERVO_MAX => Ok(position.to_string()),
+        Ok(position) => Err(format!("position {position} is above {SERVO_MAX}")),
+        Err(_) => Err(format!("invalid position `{value}`")),
+    }
+}
+
+fn parse_color(value: &str) -> Result<String, String> {
+    let channels = value
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6)
+        .and_then(|hex| {
+            let channel = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+            Some([channel(0)?, channel(2)?, channel(4)?])
+        })
+        .ok_or_else(|| format!("invalid color `{value}`, expected `#rrggbb`"))?;
+
+    Ok(format!(
+        "rgb::RGB8::new({}, {}, {})",
+        channels[0], channels[1], channels[2]
+    ))
+}
+
+fn parse_easing(easing: &str) -> Option<&'static str> {
+    match easing {
+        "linear" => Some("Linear"),
+        "cubic-in-out" => Some("CubicInOut"),
+        _ => None,
+    }
+}
+
+fn to_camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn render_animations(animations: &[OwlAnimation]) -> String {
+    let mut out = String::new();
+
+    writeln!(
+        out,
+        "// Generated by build.rs from the .owl files in {ANIMATIONS_DIR}."
+    )
+    .unwrap();
+
+    for animation in animations {
+        let OwlSource::Frames(frames) = &animation.source else {
+            continue;
+        };
+
+        writeln!(out).unwrap();
+        writeln!(out, "pub static {}: &Animation = &[", animation.table).unwrap();
+        for frame in frames {
+            writeln!(out, "    {},", render_frame(frame.as_ref())).unwrap();
+        }
+        writeln!(out, "];").unwrap();
+    }
+
+    writeln!(out).unwrap();
+    writeln!(out, "#[derive(Clone, Copy)]").unwrap();
+    writeln!(out, "pub enum AnimationType {{").unwrap();
+    for animation in animations {
+        writeln!(out, "    {},", animation.variant).unwrap();
+    }
+    writeln!(out, "    Recorded(usize),").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    writeln!(out).unwrap();
+    writeln!(out, "impl AnimationType {{").unwrap();
+    writeln!(
+        out,
+        "    pub fn get_animation(&self) -> alloc::borrow::Cow<'static, Animation> {{"
+    )
+    .unwrap();
+    writeln!(out, "        match self {{").unwrap();
+    for animation in animations {
+        let value = match &animation.source {
+            OwlSource::Frames(_) => format!("alloc::borrow::Cow::Borrowed({})", animation.table),
+            OwlSource::Script(script) => {
+                format!("alloc::borrow::Cow::Owned(script::compile(&{script}))")
+            }
+        };
+        writeln!(
+            out,
+            "            AnimationType::{} => {},",
+            animation.variant, value
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "            AnimationType::Recorded(index) => \
+         alloc::borrow::Cow::Owned(recordings::get(*index).unwrap_or_default()),"
+    )
+    .unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+
+    writeln!(out).unwrap();
+    writeln!(out, "    pub fn get_name(&self) -> &'static str {{").unwrap();
+    writeln!(out, "        match self {{").unwrap();
+    for animation in animations {
+        writeln!(
+            out,
+            "            AnimationType::{} => \"{}\",",
+            animation.variant, animation.name
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "            AnimationType::Recorded(_) => \"recorded\","
+    )
+    .unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+
+    writeln!(out).unwrap();
+    writeln!(
+        out,
+        "    pub fn get_from_binary(payload: &[u8]) -> Option<AnimationType> {{"
+    )
+    .unwrap();
+    writeln!(out, "        match payload {{").unwrap();
+    for animation in animations {
+        writeln!(
+            out,
+            "            b\"{}\" => Some(AnimationType::{}),",
+            animation.name, animation.variant
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "            _ => recordings::find(payload).map(AnimationType::Recorded),"
+    )
+    .unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    out
+}
+
+fn render_frame(frame: Option<&OwlFrame>) -> String {
+    let Some(frame) = frame else {
+        return "None".to_string();
+    };
+
+    let mut fields = Vec::new();
+    for (track, keyframe) in frame.tracks.iter().enumerate() {
+        match keyframe {
+            Some((value, easing)) => fields.push(format!(
+                "{}: Some(({}, Easing::{}))",
+                TRACK_FIELDS[track], value, easing
+            )),
+            None if !frame.default => fields.push(format!("{}: None", TRACK_FIELDS[track])),
+            None => (),
+        }
+    }
+
+    match &frame.audio {
+        Some(track) => fields.push(format!(
+            "audio: Some(crate::modules::audio::tracks::Tracks::{track})"
+        )),
+        None if !frame.default => fields.push("audio: None".to_string()),
+        None => (),
+    }
+
+    match (frame.default, fields.is_empty()) {
+        (true, true) => "Some(Frame::default())".to_string(),
+        (true, false) => format!(
+            "Some(Frame {{ {}, ..Frame::default() }})",
+            fields.join(", ")
+        ),
+        (false, _) => format!("Some(Frame {{ {} }})", fields.join(", ")),
+    }
+}
@@ -0,0 +1,39 @@
+/// Sample rate of the speaker.
+pub const SAMPLE_RATE: u32 = 16_000;
+
+/// Fractional bits of a gain, [`UNITY_GAIN`] plays samples unchanged.
+pub const GAIN_SHIFT: u32 = 8;
+pub const UNITY_GAIN: u32 = 1 << GAIN_SHIFT;
+/// Highest gain once every volume is applied, keeps the samples in range of an `i32`.
+pub const MAX_GAIN: u32 = 16 * UNITY_GAIN;
+
+/// Volumes are in percent, above 100 boosts quiet sources.
+pub const MAX_VOLUME: u8 = 200;
+
+/// Samples above this are pushed smoothly towards full scale instead of clipping.
+pub const LIMITER_THRESHOLD: i32 = 24_000;
+
+/// RMS streams are brought to, about -20 dBFS.
+pub const NORMALIZE_TARGET: f32 = 3_300.0;
+/// Chunks quieter than this RMS are pauses, they don't change the loudness.
+pub const NORMALIZE_SILENCE: f32 = 200.0;
+/// Weight of a new chunk in the loudness when it is louder, so peaks are caught quickly.
+pub const NORMALIZE_ATTACK: f32 = 0.5;
+/// Weight of a new chunk in the loudness when it is quieter.
+pub const NORMALIZE_RELEASE: f32 = 0.05;
+/// Normalizing never cuts or boosts more than this.
+pub const NORMALIZE_MIN_GAIN: u32 = UNITY_GAIN / 4;
+pub const NORMALIZE_MAX_GAIN: u32 = 4 * UNITY_GAIN;
+
+/// Input samples every output sample of the resampler is filtered from.
+pub const RESAMPLER_TAPS: usize = 48;
+/// Fractional positions between two input samples the filter is precomputed for.
+pub const RESAMPLER_PHASES: usize = 32;
+/// Cutoff of the resampler, as a fraction of the lower Nyquist frequency, leaves room for the
+/// filter to fall off before aliasing.
+pub const RESAMPLER_ROLLOFF: f32 = 0.9;
+
+/// Lowest and highest sample rates sources can have.
+pub const MIN_SOURCE_RATE: u32 = 8_000;
+pub const MAX_SOURCE_RATE: u32 = 48_000;
+pub const MAX_SOURCE_CHANNELS: u8 = 2;
@@ -0,0 +1,210 @@
+use super::config::{MAX_SOURCE_CHANNELS, MAX_SOURCE_RATE, MIN_SOURCE_RATE, SAMPLE_RATE};
+
+/// Layout of signed 16-bit little-endian PCM, channels are interleaved.
+#[derive(Clone, Copy, PartialEq)]
+pub struct PcmFormat {
+    pub sample_rate: u32,
+    pub channels: u8,
+}
+
+impl PcmFormat {
+    /// What the speaker plays, sources in any other format are converted.
+    pub const OUTPUT: PcmFormat = PcmFormat {
+        sample_rate: SAMPLE_RATE,
+        channels: 1,
+    };
+
+    pub const fn new(sample_rate: u32, channels: u8) -> Self {
+        Self {
+            sample_rate,
+            channels,
+        }
+    }
+
+    /// Parse `<sample rate>` or `<sample rate>:<channels>`, like `44100:2`.
+    pub fn get_from_binary(payload: &[u8]) -> Option<PcmFormat> {
+        let payload = core::str::from_utf8(payload).ok()?;
+        let (sample_rate, channels) = match payload.split_once(':') {
+            Some((sample_rate, channels)) => (sample_rate, channels.parse().ok()?),
+            None => (payload, 1),
+        };
+        let sample_rate = sample_rate.parse().ok()?;
+
+        let valid = (MIN_SOURCE_RATE..=MAX_SOURCE_RATE).contains(&sample_rate)
+            && (1..=MAX_SOURCE_CHANNELS).contains(&channels);
+        valid.then_some(PcmFormat::new(sample_rate, channels))
+    }
+
+    /// Bytes of one sample of every channel.
+    pub fn get_frame_size(&self) -> usize {
+        2 * self.channels as usize
+    }
+
+    /// Average the channels of one frame, see [`Self::get_frame_size`].
+    pub fn downmix(&self, frame: &[u8]) -> i16 {
+        let sum: i32 = frame
+            .chunks_exact(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]) as i32)
+            .sum();
+        (sum / self.channels as i32) as i16
+    }
+
+    /// Every frame of `pcm` as a mono sample.
+    pub fn samples<'a>(&self, pcm: &'a [u8]) -> impl Iterator<Item = i16> + 'a {
+        let format = *self;
+        pcm.chunks_exact(self.get_frame_size())
+            .map(move |frame| format.downmix(frame))
+    }
+}
+
+const MAX_FRAME_SIZE: usize = 2 * MAX_SOURCE_CHANNELS as usize;
+
+/// Carries a frame that is split between two chunks over to the next one.
+///
+/// Streams arrive in chunks of any size, dropping the partial frame at the end of one would
+/// swap the channels, or the bytes of every sample, in the rest of the stream.
+pub struct FrameCarry {
+    bytes: [u8; MAX_FRAME_SIZE],
+    len: usize,
+}
+
+impl Default for FrameCarry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameCarry {
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; MAX_FRAME_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Forget the carried bytes, for a new source.
+    pub fn reset(&mut self) {
+        self.len = 0;
+    }
+
+    /// Returns the frame completed with the start of `chunk` as a mono sample, and the whole
+    /// frames after it. The partial frame at the end is kept for the next chunk.
+    pub fn split<'a>(&mut self, chunk: &'a [u8], format: PcmFormat) -> (Option<i16>, &'a [u8]) {
+        let frame_size = format.get_frame_size();
+        let mut chunk = chunk;
+        let mut carried = None;
+
+        if self.len > 0 {
+            let missing = (frame_size - self.len).min(chunk.len());
+            let (start, rest) = chunk.split_at(missing);
+            self.bytes[self.len..self.len + missing].copy_from_slice(start);
+            self.len += missing;
+            chunk = rest;
+
+            if self.len == frame_size {
+                carried = Some(format.downmix(&self.bytes[..frame_size]));
+                self.len = 0;
+            }
+        }
+
+        let whole = chunk.len() - chunk.len() % frame_size;
+        let (frames, tail) = chunk.split_at(whole);
+        if !tail.is_empty() {
+            self.bytes[..tail.len()].copy_from_slice(tail);
+            self.len = tail.len();
+        }
+
+        (carried, frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    fn pcm(samples: &[i16]) -> Vec<u8> {
+        samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn downmix_averages_stereo() {
+        let format = PcmFormat::new(44_100, 2);
+        assert_eq!(format.get_frame_size(), 4);
+        assert_eq!(format.downmix(&pcm(&[1_000, 3_000])), 2_000);
+        assert_eq!(format.downmix(&pcm(&[-1_000, 1_000])), 0);
+        // The sum of full scale channels doesn't overflow
+        assert_eq!(format.downmix(&pcm(&[i16::MAX, i16::MAX])), i16::MAX);
+        assert_eq!(format.downmix(&pcm(&[i16::MIN, i16::MIN])), i16::MIN);
+    }
+
+    #[test]
+    fn samples_downmix_every_frame() {
+        let format = PcmFormat::new(48_000, 2);
+        // A trailing half frame is skipped, see FrameCarry
+        let mut stereo = pcm(&[100, 300, -200, -400, 7, 7]);
+        stereo.push(0);
+
+        let mono: Vec<i16> = format.samples(&stereo).collect();
+        assert_eq!(mono, [200, -300, 7]);
+    }
+
+    #[test]
+    fn split_frames_are_carried_over() {
+        let format = PcmFormat::new(44_100, 2);
+        let stereo = pcm(&[100, 300, -200, -400, 7, 9, 1_000, 3_000]);
+
+        // Every way of cutting the stream in two gives the same samples
+        for cut in 0..=stereo.len() {
+            let mut carry = FrameCarry::new();
+            let mut mono = Vec::new();
+            for chunk in [&stereo[..cut], &stereo[cut..]] {
+                let (carried, frames) = carry.split(chunk, format);
+                mono.extend(carried);
+                mono.extend(format.samples(frames));
+            }
+            assert_eq!(mono, [200, -300, 8, 2_000], "cut at {}", cut);
+        }
+    }
+
+    #[test]
+    fn carry_survives_tiny_chunks() {
+        let format = PcmFormat::new(22_050, 2);
+        let stereo = pcm(&[10, 30, -10, -30]);
+
+        let mut carry = FrameCarry::new();
+        let mut mono = Vec::new();
+        for byte in stereo.chunks(1) {
+            let (carried, frames) = carry.split(byte, format);
+            assert!(frames.is_empty());
+            mono.extend(carried);
+        }
+        assert_eq!(mono, [20, -20]);
+
+        // A new source doesn't start with the bytes of the last one
+        carry.split(&stereo[..3], format);
+        carry.reset();
+        let (carried, frames) = carry.split(&stereo[..4], format);
+        assert!(carried.is_none());
+        assert_eq!(format.samples(frames).collect::<Vec<_>>(), [20]);
+    }
+
+    #[test]
+    fn mono_is_unchanged() {
+        let mono: Vec<i16> = PcmFormat::OUTPUT.samples(&pcm(&[1, -2, 3])).collect();
+        assert_eq!(mono, [1, -2, 3]);
+    }
+
+    #[test]
+    fn formats_are_parsed() {
+        assert!(PcmFormat::get_from_binary(b"44100:2") == Some(PcmFormat::new(44_100, 2)));
+        assert!(PcmFormat::get_from_binary(b"22050") == Some(PcmFormat::new(22_050, 1)));
+        assert!(PcmFormat::get_from_binary(b"96000").is_none());
+        assert!(PcmFormat::get_from_binary(b"16000:3").is_none());
+        assert!(PcmFormat::get_from_binary(b"fast").is_none());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod config;
+pub mod format;
+pub mod resampler;
+pub mod volume;
@@ -0,0 +1,251 @@
+use core::f32::consts::PI;
+
+use super::config::{RESAMPLER_PHASES, RESAMPLER_ROLLOFF, RESAMPLER_TAPS};
+
+/// Fractional bits of positions between input samples.
+const POSITION_SHIFT: u32 = 16;
+const POSITION_ONE: u32 = 1 << POSITION_SHIFT;
+/// Fractional bits of the filter coefficients, leaves headroom for the sum of all taps.
+const COEFFICIENT_SHIFT: u32 = 14;
+
+/// Coefficients for every fractional position, the last one is a whole input sample further so
+/// every position lies between two of them.
+type Filter = [[i16; RESAMPLER_TAPS]; RESAMPLER_PHASES + 1];
+
+/// Converts mono samples from one sample rate to another with a polyphase windowed sinc
+/// filter, which also keeps frequencies the output can't hold from folding back as aliases.
+pub struct Resampler {
+    /// Input samples per output sample.
+    step: u32,
+    /// Time of the next output sample after the center of the history, in input samples.
+    position: u32,
+    /// Last input samples, oldest first.
+    history: [i16; RESAMPLER_TAPS],
+    /// `None` when the rates are the same.
+    filter: Option<Filter>,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        let step = ((input_rate as u64) << POSITION_SHIFT) / output_rate as u64;
+        let filter = (input_rate != output_rate).then(|| {
+            // Only the lower of the two rates limits the bandwidth
+            let cutoff = RESAMPLER_ROLLOFF * (output_rate as f32 / input_rate as f32).min(1.0);
+            Self::design_filter(cutoff)
+        });
+
+        Self {
+            step: step as u32,
+            position: 0,
+            history: [0; RESAMPLER_TAPS],
+            filter,
+        }
+    }
+
+    /// Lowpass at `cutoff` times the input Nyquist frequency, one row of taps per phase.
+    fn design_filter(cutoff: f32) -> Filter {
+        let mut filter = [[0; RESAMPLER_TAPS]; RESAMPLER_PHASES + 1];
+        let half_width = (RESAMPLER_TAPS / 2) as f32;
+
+        for (phase, row) in filter.iter_mut().enumerate() {
+            let fraction = phase as f32 / RESAMPLER_PHASES as f32;
+            let mut taps = [0.0; RESAMPLER_TAPS];
+
+            for (tap, value) in taps.iter_mut().enumerate() {
+                // Distance from the output sample, which sits between the two center taps
+                let x = tap as f32 - (half_width - 1.0) - fraction;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    libm::sinf(PI * cutoff * x) / (PI * cutoff * x)
+                };
+                // Hann window
+                let window = 0.5 + 0.5 * libm::cosf(PI * x / half_width);
+                *value = cutoff * sinc * window.max(0.0);
+            }
+
+            // Keep the gain at DC exactly one for every phase
+            let sum: f32 = taps.iter().sum();
+            for (coefficient, value) in row.iter_mut().zip(taps) {
+                *coefficient = libm::roundf(value / sum * (1 << COEFFICIENT_SHIFT) as f32)
+                    .clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+            }
+        }
+
+        filter
+    }
+
+    /// Largest number of input samples that can't make more than `output` samples.
+    pub fn get_max_input(&self, output: usize) -> usize {
+        let input = (output.saturating_sub(1) as u64 * self.step as u64) >> POSITION_SHIFT;
+        (input as usize).max(1)
+    }
+
+    /// Take one input sample, `output` is called for every output sample it completes.
+    pub fn push(&mut self, sample: i16, mut output: impl FnMut(i16)) {
+        let Some(filter) = &self.filter else {
+            output(sample);
+            return;
+        };
+
+        self.history.copy_within(1.., 0);
+        self.history[RESAMPLER_TAPS - 1] = sample;
+
+        while self.position < POSITION_ONE {
+            // Snapping to one phase would jitter the timing, blend the two around the position
+            let scaled = self.position as usize * RESAMPLER_PHASES;
+            let phase = scaled >> POSITION_SHIFT;
+            let fraction = (scaled & (POSITION_ONE as usize - 1)) as i64;
+
+            let before = self.filter_at(&filter[phase]) as i64;
+            let after = self.filter_at(&filter[phase + 1]) as i64;
+            let sum = before + (((after - before) * fraction) >> POSITION_SHIFT);
+
+            output((sum >> COEFFICIENT_SHIFT).clamp(i16::MIN as i64, i16::MAX as i64) as i16);
+            self.position += self.step;
+        }
+
+        self.position -= POSITION_ONE;
+    }
+
+    fn filter_at(&self, coefficients: &[i16; RESAMPLER_TAPS]) -> i32 {
+        coefficients
+            .iter()
+            .zip(self.history)
+            .map(|(&coefficient, sample)| coefficient as i32 * sample as i32)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::audio::config::SAMPLE_RATE;
+
+    const SOURCE_RATES: [u32; 5] = [8_000, 22_050, 24_000, 44_100, 48_000];
+    const AMPLITUDE: f32 = 16_000.0;
+
+    /// Resample half a second of a sine, without the start where the filter fills up.
+    ///
+    /// Also returns the angle the tone advances by per output sample, the step between output
+    /// samples is rounded so it is slightly off the nominal frequency.
+    fn resample_tone(input_rate: u32, frequency: f32) -> (Vec<f32>, f32) {
+        let mut resampler = Resampler::new(input_rate, SAMPLE_RATE);
+        let mut output = Vec::new();
+
+        for n in 0..input_rate / 2 {
+            let phase = 2.0 * PI * frequency * n as f32 / input_rate as f32;
+            let sample = libm::roundf(AMPLITUDE * libm::sinf(phase)) as i16;
+            resampler.push(sample, |sample| output.push(sample as f32));
+        }
+
+        let step = resampler.step as f32 / POSITION_ONE as f32;
+        let omega = 2.0 * PI * frequency * step / input_rate as f32;
+        (output.split_off(SAMPLE_RATE as usize / 10), omega)
+    }
+
+    fn to_db(amplitude: f32) -> f32 {
+        20.0 * libm::log10f(amplitude / AMPLITUDE)
+    }
+
+    /// Level of `frequency` in `output` and of everything else, both in dB of the input tone.
+    fn analyze(output: &[f32], omega: f32) -> (f32, f32) {
+        let (mut sine, mut cosine) = (0.0, 0.0);
+        for (n, sample) in output.iter().enumerate() {
+            sine += sample * libm::sinf(omega * n as f32);
+            cosine += sample * libm::cosf(omega * n as f32);
+        }
+        sine *= 2.0 / output.len() as f32;
+        cosine *= 2.0 / output.len() as f32;
+
+        let residual: f32 = output
+            .iter()
+            .enumerate()
+            .map(|(n, sample)| {
+                let tone =
+                    sine * libm::sinf(omega * n as f32) + cosine * libm::cosf(omega * n as f32);
+                (sample - tone) * (sample - tone)
+            })
+            .sum();
+        // Peak amplitude of a sine with the same power
+        let residual = libm::sqrtf(2.0 * residual / output.len() as f32);
+
+        (to_db(libm::hypotf(sine, cosine)), to_db(residual))
+    }
+
+    /// Peak amplitude of a sine with the same power as `output`, in dB of the input tone.
+    fn level(output: &[f32]) -> f32 {
+        let power: f32 = output.iter().map(|sample| sample * sample).sum();
+        to_db(libm::sqrtf(2.0 * power / output.len() as f32))
+    }
+
+    #[test]
+    fn passband_is_flat() {
+        for input_rate in SOURCE_RATES {
+            let nyquist = input_rate.min(SAMPLE_RATE) as f32 / 2.0;
+            let sweep = [
+                100.0, 250.0, 500.0, 1_000.0, 2_000.0, 3_000.0, 4_000.0, 5_000.0,
+            ];
+
+            for frequency in sweep
+                .into_iter()
+                .filter(|frequency| *frequency <= 0.7 * nyquist)
+            {
+                let (output, omega) = resample_tone(input_rate, frequency);
+                let (gain, distortion) = analyze(&output, omega);
+
+                assert!(
+                    gain.abs() < 0.5,
+                    "{input_rate} Hz: {frequency} Hz tone changed by {gain:.2} dB"
+                );
+                // Images, aliases and timing jitter all end up outside the tone
+                assert!(
+                    distortion < -55.0,
+                    "{input_rate} Hz: {frequency} Hz tone has {distortion:.1} dB of distortion"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn aliases_are_rejected() {
+        for input_rate in SOURCE_RATES.into_iter().filter(|rate| *rate > SAMPLE_RATE) {
+            // Everything a kilohertz above the output Nyquist frequency would fold back
+            let mut frequency = SAMPLE_RATE as f32 / 2.0 + 1_000.0;
+            while frequency < input_rate as f32 / 2.0 {
+                let (output, _) = resample_tone(input_rate, frequency);
+                let alias = level(&output);
+
+                assert!(
+                    alias < -40.0,
+                    "{input_rate} Hz: {frequency} Hz tone aliases at {alias:.1} dB"
+                );
+                frequency += 1_000.0;
+            }
+        }
+    }
+
+    #[test]
+    fn same_rate_passes_through() {
+        let mut resampler = Resampler::new(SAMPLE_RATE, SAMPLE_RATE);
+        let mut output = Vec::new();
+        for sample in [0, 1_000, -32_768, 32_767] {
+            resampler.push(sample, |sample| output.push(sample));
+        }
+        assert_eq!(output, [0, 1_000, -32_768, 32_767]);
+    }
+
+    #[test]
+    fn output_count_follows_the_rates() {
+        for input_rate in SOURCE_RATES {
+            let (output, _) = resample_tone(input_rate, 1_000.0);
+            let expected = SAMPLE_RATE / 2 - SAMPLE_RATE / 10;
+            assert!(
+                output.len().abs_diff(expected as usize) <= 1,
+                "{input_rate} Hz"
+            );
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use super::config::*;
+
+/// Volumes in percent, stored in the settings.
+#[derive(Clone, Copy)]
+pub struct Volume {
+    pub master: u8,
+    pub track: u8,
+    pub stream: u8,
+    /// Bring streams to the same loudness, see [`Normalizer`].
+    pub normalize_streams: bool,
+}
+
+impl Volume {
+    pub const DEFAULT: Volume = Volume {
+        master: 100,
+        track: 100,
+        stream: 100,
+        normalize_streams: false,
+    };
+
+    pub fn get(&self, channel: VolumeChannel) -> u8 {
+        match channel {
+            VolumeChannel::Master => self.master,
+            VolumeChannel::Track => self.track,
+            VolumeChannel::Stream => self.stream,
+        }
+    }
+
+    pub fn set(&mut self, channel: VolumeChannel, percent: u8) {
+        let percent = percent.min(MAX_VOLUME);
+        match channel {
+            VolumeChannel::Master => self.master = percent,
+            VolumeChannel::Track => self.track = percent,
+            VolumeChannel::Stream => self.stream = percent,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum VolumeChannel {
+    /// Applies to everything.
+    Master,
+    Track,
+    Stream,
+}
+
+impl VolumeChannel {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            VolumeChannel::Master => "master",
+            VolumeChannel::Track => "track",
+            VolumeChannel::Stream => "stream",
+        }
+    }
+
+    pub fn get_from_binary(payload: &[u8]) -> Option<VolumeChannel> {
+        match payload {
+            b"master" => Some(VolumeChannel::Master),
+            b"track" => Some(VolumeChannel::Track),
+            b"stream" => Some(VolumeChannel::Stream),
+            _ => None,
+        }
+    }
+}
+
+pub fn percent_to_gain(percent: u8) -> u32 {
+    percent as u32 * UNITY_GAIN / 100
+}
+
+/// Gain of two gains applied one after the other.
+pub fn combine(a: u32, b: u32) -> u32 {
+    ((a * b) >> GAIN_SHIFT).min(MAX_GAIN)
+}
+
+/// Soft knee limiter, maps any sample into range without a hard edge.
+///
+/// Samples under [`LIMITER_THRESHOLD`] are left alone, the ones above get closer to full
+/// scale the louder they are but never reach it.
+pub fn limit(sample: i32) -> i16 {
+    let magnitude = sample.unsigned_abs() as i64;
+    let threshold = LIMITER_THRESHOLD as i64;
+    if magnitude <= threshold {
+        return sample as i16;
+    }
+
+    let over = magnitude - threshold;
+    let headroom = i16::MAX as i64 - threshold;
+    let limited = (threshold + over * headroom / (over + headroom)) as i32;
+
+    if sample < 0 {
+        -limited as i16
+    } else {
+        limited as i16
+    }
+}
+
+pub fn apply_gain(sample: i16, gain: u32) -> i16 {
+    limit((sample as i32 * gain as i32) >> GAIN_SHIFT)
+}
+
+/// Follows the loudness of a stream and finds the gain that brings it to
+/// [`NORMALIZE_TARGET`].
+pub struct Normalizer {
+    /// Mean square of the samples, `None` until something louder than silence was heard.
+    level: Option<f32>,
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Normalizer {
+    pub const fn new() -> Self {
+        Self { level: None }
+    }
+
+    /// Forget the loudness, for a new stream.
+    pub fn reset(&mut self) {
+        self.level = None;
+    }
+
+    /// Measure a chunk of samples, returns the gain to play it with.
+    pub fn update(&mut self, samples: impl Iterator<Item = i16>) -> u32 {
+        let mut sum = 0.0;
+        let mut count = 0;
+        for sample in samples {
+            sum += sample as f32 * sample as f32;
+            count += 1;
+        }
+
+        if count > 0 {
+            let mean_square = sum / count as f32;
+            if mean_square > NORMALIZE_SILENCE * NORMALIZE_SILENCE {
+                self.level = Some(match self.level {
+                    Some(level) => {
+                        let weight = if mean_square > level {
+                            NORMALIZE_ATTACK
+                        } else {
+                            NORMALIZE_RELEASE
+                        };
+                        level + (mean_square - level) * weight
+                    }
+                    None => mean_square,
+                });
+            }
+        }
+
+        match self.level {
+            Some(level) => {
+                let gain = NORMALIZE_TARGET / libm::sqrtf(level) * UNITY_GAIN as f32;
+                (gain as u32).clamp(NORMALIZE_MIN_GAIN, NORMALIZE_MAX_GAIN)
+            }
+            None => UNITY_GAIN,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Every step of this size from one end of an `i32` to the other, and both ends.
+    fn samples(step: usize) -> Vec<i32> {
+        let mut samples: Vec<i32> = (i32::MIN..=i32::MAX).step_by(step).collect();
+        samples.push(i32::MAX);
+        samples
+    }
+
+    #[test]
+    fn limit_is_monotonic_and_stays_below_full_scale() {
+        let mut previous = i16::MIN;
+        for sample in samples(1 << 12) {
+            let limited = limit(sample);
+            assert!(limited >= previous, "{} went down to {}", sample, limited);
+            assert!(
+                limited > -i16::MAX && limited < i16::MAX,
+                "{} hit full scale",
+                sample
+            );
+            previous = limited;
+        }
+    }
+
+    #[test]
+    fn limit_leaves_quiet_samples_alone() {
+        for sample in [-LIMITER_THRESHOLD, -1, 0, 1, LIMITER_THRESHOLD] {
+            assert_eq!(limit(sample) as i32, sample);
+        }
+    }
+
+    #[test]
+    fn full_scale_at_max_gain_stays_in_range() {
+        for sample in [i16::MIN, -1, 1, i16::MAX] {
+            let played = apply_gain(sample, MAX_GAIN);
+            assert_eq!(played.signum(), sample.signum());
+            assert!(played > -i16::MAX && played < i16::MAX);
+        }
+    }
+
+    #[test]
+    fn combine_clamps_at_max_gain() {
+        assert_eq!(combine(UNITY_GAIN, UNITY_GAIN / 2), UNITY_GAIN / 2);
+        assert_eq!(combine(MAX_GAIN, UNITY_GAIN), MAX_GAIN);
+        assert_eq!(combine(MAX_GAIN, MAX_GAIN), MAX_GAIN);
+        assert_eq!(combine(percent_to_gain(MAX_VOLUME), MAX_GAIN), MAX_GAIN);
+    }
+
+    fn chunk(amplitude: i16) -> impl Iterator<Item = i16> {
+        // A square wave, its RMS is the amplitude
+        (0..256).map(move |index| {
+            if index % 2 == 0 {
+                amplitude
+            } else {
+                -amplitude
+            }
+        })
+    }
+
+    #[test]
+    fn normalizer_clamps_its_gain() {
+        let mut normalizer = Normalizer::new();
+        assert_eq!(normalizer.update(chunk(i16::MAX)), NORMALIZE_MIN_GAIN);
+
+        normalizer.reset();
+        let quiet = NORMALIZE_SILENCE as i16 + 50;
+        assert_eq!(normalizer.update(chunk(quiet)), NORMALIZE_MAX_GAIN);
+
+        // The target loudness plays unchanged
+        normalizer.reset();
+        let gain = normalizer.update(chunk(NORMALIZE_TARGET as i16));
+        assert!(gain.abs_diff(UNITY_GAIN) <= 1);
+    }
+
+    #[test]
+    fn normalizer_ignores_silence() {
+        let mut normalizer = Normalizer::new();
+        assert_eq!(normalizer.update(chunk(0)), UNITY_GAIN);
+        assert_eq!(
+            normalizer.update(chunk(NORMALIZE_SILENCE as i16 - 1)),
+            UNITY_GAIN
+        );
+        assert_eq!(normalizer.update(core::iter::empty()), UNITY_GAIN);
+
+        // Pauses keep the gain the stream had before them
+        let gain = normalizer.update(chunk(8_000));
+        for _ in 0..100 {
+            assert_eq!(normalizer.update(chunk(10)), gain);
+        }
+    }
+}
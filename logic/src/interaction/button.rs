@@ -0,0 +1,289 @@
+/// Completed presses of a button.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ButtonEvent {
+    /// Pressed and let go once.
+    Click,
+    /// Two clicks close together.
+    DoubleClick,
+    /// Let go after holding it for a while.
+    LongPress,
+    /// Sent every repeat period while held, after the long press time.
+    HoldRepeat,
+    /// Sent once while still held, letting go after it sends nothing else.
+    VeryLongPress,
+}
+
+impl ButtonEvent {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ButtonEvent::Click => "click",
+            ButtonEvent::DoubleClick => "double click",
+            ButtonEvent::LongPress => "long press",
+            ButtonEvent::HoldRepeat => "hold repeat",
+            ButtonEvent::VeryLongPress => "very long press",
+        }
+    }
+}
+
+pub struct ButtonTiming {
+    /// Longest time between letting go and pressing again for a double click.
+    pub double_click_ms: u64,
+    pub long_press_ms: u64,
+    pub repeat_ms: u64,
+    pub very_long_press_ms: u64,
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Idle,
+    Pressed {
+        since_ms: u64,
+        /// Second press of a double click.
+        second: bool,
+        next_repeat_ms: u64,
+        very_long_reported: bool,
+    },
+    /// Clicked once, waiting to see whether a second click follows.
+    Clicked {
+        released_ms: u64,
+    },
+}
+
+/// Turns the debounced state of a button into click, double click and long press events.
+pub struct Button {
+    timing: ButtonTiming,
+    state: State,
+}
+
+impl Button {
+    pub const fn new(timing: ButtonTiming) -> Self {
+        Self {
+            timing,
+            state: State::Idle,
+        }
+    }
+
+    /// Feed the state of the button on every edge and at the deadline. Returns an event when
+    /// one was completed.
+    pub fn update(&mut self, now: u64, pressed: bool) -> Option<ButtonEvent> {
+        match (self.state, pressed) {
+            (State::Idle, false) => None,
+            (State::Idle, true) => {
+                self.press(now, false);
+                None
+            }
+            (State::Clicked { released_ms }, pressed) => {
+                let expired = now - released_ms > self.timing.double_click_ms;
+                if pressed {
+                    self.press(now, !expired);
+                } else if expired {
+                    self.state = State::Idle;
+                }
+                // The first click is only reported once no second one can follow
+                expired.then_some(ButtonEvent::Click)
+            }
+            (
+                State::Pressed {
+                    since_ms,
+                    second,
+                    very_long_reported,
+                    ..
+                },
+                false,
+            ) => {
+                self.state = State::Idle;
+                let held = now - since_ms;
+
+                if very_long_reported {
+                    None
+                } else if held >= self.timing.very_long_press_ms {
+                    Some(ButtonEvent::VeryLongPress)
+                } else if held >= self.timing.long_press_ms {
+                    Some(ButtonEvent::LongPress)
+                } else if second {
+                    Some(ButtonEvent::DoubleClick)
+                } else {
+                    self.state = State::Clicked { released_ms: now };
+                    None
+                }
+            }
+            (
+                State::Pressed {
+                    since_ms,
+                    second,
+                    next_repeat_ms,
+                    very_long_reported,
+                },
+                true,
+            ) => {
+                if very_long_reported {
+                    return None;
+                }
+
+                if now - since_ms >= self.timing.very_long_press_ms {
+                    self.state = State::Pressed {
+                        since_ms,
+                        second,
+                        next_repeat_ms,
+                        very_long_reported: true,
+                    };
+                    return Some(ButtonEvent::VeryLongPress);
+                }
+
+                if now < next_repeat_ms {
+                    return None;
+                }
+
+                self.state = State::Pressed {
+                    since_ms,
+                    second,
+                    next_repeat_ms: next_repeat_ms + self.timing.repeat_ms,
+                    very_long_reported,
+                };
+                Some(ButtonEvent::HoldRepeat)
+            }
+        }
+    }
+
+    /// When to call [`Button::update`] again if the button doesn't change.
+    pub fn get_deadline(&self) -> Option<u64> {
+        match self.state {
+            State::Idle => None,
+            State::Pressed {
+                very_long_reported: true,
+                ..
+            } => None,
+            State::Pressed {
+                since_ms,
+                next_repeat_ms,
+                ..
+            } => Some(next_repeat_ms.min(since_ms + self.timing.very_long_press_ms)),
+            State::Clicked { released_ms } => Some(released_ms + self.timing.double_click_ms + 1),
+        }
+    }
+
+    fn press(&mut self, now: u64, second: bool) {
+        self.state = State::Pressed {
+            since_ms: now,
+            second,
+            next_repeat_ms: now + self.timing.long_press_ms,
+            very_long_reported: false,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use ButtonEvent::*;
+
+    const TIMING: ButtonTiming = ButtonTiming {
+        double_click_ms: 300,
+        long_press_ms: 800,
+        repeat_ms: 500,
+        very_long_press_ms: 5_000,
+    };
+
+    /// Run a timeline of edges through a button like the interaction task does, waking up at
+    /// every edge and at every deadline until `end_ms`.
+    fn replay(edges: &[(u64, bool)], end_ms: u64) -> Vec<(u64, ButtonEvent)> {
+        let mut button = Button::new(TIMING);
+        let mut edges = edges.iter().copied().peekable();
+        let mut pressed = false;
+        let mut events = Vec::new();
+
+        loop {
+            let deadline = button.get_deadline().filter(|deadline| *deadline <= end_ms);
+            let now = match (edges.peek(), deadline) {
+                (Some(&(edge_ms, _)), Some(deadline)) if deadline < edge_ms => deadline,
+                (Some(&(edge_ms, state)), _) => {
+                    edges.next();
+                    pressed = state;
+                    edge_ms
+                }
+                (None, Some(deadline)) => deadline,
+                (None, None) => return events,
+            };
+
+            if let Some(event) = button.update(now, pressed) {
+                events.push((now, event));
+            }
+        }
+    }
+
+    #[test]
+    fn click_waits_for_a_second_click() {
+        let events = replay(&[(0, true), (100, false)], 10_000);
+        assert_eq!(events, [(401, Click)]);
+    }
+
+    #[test]
+    fn double_click() {
+        let events = replay(
+            &[(0, true), (100, false), (250, true), (350, false)],
+            10_000,
+        );
+        assert_eq!(events, [(350, DoubleClick)]);
+    }
+
+    #[test]
+    fn slow_clicks_are_two_clicks() {
+        let edges = [(0, true), (100, false), (600, true), (700, false)];
+        assert_eq!(replay(&edges, 10_000), [(401, Click), (1_001, Click)]);
+    }
+
+    #[test]
+    fn long_press() {
+        let events = replay(&[(0, true), (1_000, false)], 10_000);
+        assert_eq!(events, [(800, HoldRepeat), (1_000, LongPress)]);
+    }
+
+    #[test]
+    fn long_second_press_is_no_double_click() {
+        let edges = [(0, true), (100, false), (250, true), (1_250, false)];
+        assert_eq!(
+            replay(&edges, 10_000),
+            [(1_050, HoldRepeat), (1_250, LongPress)]
+        );
+    }
+
+    #[test]
+    fn hold_repeats() {
+        let events = replay(&[(0, true), (2_000, false)], 10_000);
+        assert_eq!(
+            events,
+            [
+                (800, HoldRepeat),
+                (1_300, HoldRepeat),
+                (1_800, HoldRepeat),
+                (2_000, LongPress),
+            ]
+        );
+    }
+
+    #[test]
+    fn very_long_press_is_sent_while_held() {
+        let events = replay(&[(0, true)], 10_000);
+        let repeats = events
+            .iter()
+            .filter(|(_, event)| *event == HoldRepeat)
+            .count();
+
+        assert_eq!(repeats, 9);
+        assert_eq!(events.last(), Some(&(5_000, VeryLongPress)));
+    }
+
+    #[test]
+    fn release_after_very_long_press_sends_nothing() {
+        let edges = [(0, true), (6_000, false), (7_000, true), (7_100, false)];
+        let events = replay(&edges, 10_000);
+
+        // Nothing between the very long press and the next click
+        assert_eq!(
+            events[events.len() - 2..],
+            [(5_000, VeryLongPress), (7_401, Click)]
+        );
+    }
+}
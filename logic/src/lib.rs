@@ -0,0 +1,14 @@
+//! Everything the firmware decides without touching hardware, from button presses to gestures,
+//! animation scripts and audio conversion. Builds on the host, run the tests with `cargo test`
+//! in this directory.
+#![no_std]
+
+extern crate alloc;
+#[cfg(test)]
+extern crate std;
+
+pub mod audio;
+pub mod interaction;
+pub mod motion;
+pub mod servo;
+pub mod touch;
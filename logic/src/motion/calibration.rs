@@ -0,0 +1,243 @@
+use super::config::{CALIBRATION_MAX_DEVIATION, CALIBRATION_SAMPLES};
+use super::gesture::ImuSample;
+
+/// Offsets of the resting sensor and the direction gravity pulls in.
+///
+/// A single resting pose can't tell accelerometer bias apart from a tilted mount, so the
+/// offset only corrects the magnitude of gravity and the tilt is rotated away instead.
+#[derive(Clone, Copy, PartialEq)]
+pub struct ImuCalibration {
+    pub accel_offset: [f32; 3],
+    pub gyro_offset: [f32; 3],
+    /// Unit vector of gravity as measured by the sensor.
+    pub gravity: [f32; 3],
+}
+
+impl ImuCalibration {
+    pub const NONE: ImuCalibration = ImuCalibration {
+        accel_offset: [0.0; 3],
+        gyro_offset: [0.0; 3],
+        gravity: [1.0, 0.0, 0.0],
+    };
+
+    /// Remove the offsets and rotate the readings so gravity points along axis 0.
+    pub fn apply(&self, sample: &mut ImuSample) {
+        let rotation = rotation_to_up(self.gravity);
+
+        for axis in 0..3 {
+            sample.accel[axis] -= self.accel_offset[axis];
+            sample.gyro[axis] -= self.gyro_offset[axis];
+        }
+
+        sample.accel = rotate(&rotation, sample.accel);
+        sample.gyro = rotate(&rotation, sample.gyro);
+    }
+}
+
+/// Averages samples of the resting sensor into a calibration.
+pub struct Calibrator {
+    count: usize,
+    accel_sum: [f32; 3],
+    gyro_sum: [f32; 3],
+    magnitude_min: f32,
+    magnitude_max: f32,
+}
+
+impl Default for Calibrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Calibrator {
+    pub const fn new() -> Self {
+        Self {
+            count: 0,
+            accel_sum: [0.0; 3],
+            gyro_sum: [0.0; 3],
+            magnitude_min: f32::MAX,
+            magnitude_max: 0.0,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.count >= CALIBRATION_SAMPLES
+    }
+
+    pub fn add(&mut self, sample: &ImuSample) {
+        for axis in 0..3 {
+            self.accel_sum[axis] += sample.accel[axis];
+            self.gyro_sum[axis] += sample.gyro[axis];
+        }
+
+        let magnitude = length(sample.accel);
+        self.magnitude_min = self.magnitude_min.min(magnitude);
+        self.magnitude_max = self.magnitude_max.max(magnitude);
+        self.count += 1;
+    }
+
+    /// Returns `None` when the owl was moved while sampling.
+    pub fn finish(&self) -> Option<ImuCalibration> {
+        if self.count == 0 || self.magnitude_max - self.magnitude_min > CALIBRATION_MAX_DEVIATION {
+            return None;
+        }
+
+        let count = self.count as f32;
+        let accel = self.accel_sum.map(|sum| sum / count);
+        let gyro_offset = self.gyro_sum.map(|sum| sum / count);
+
+        let magnitude = length(accel);
+        if magnitude == 0.0 {
+            return None;
+        }
+        let gravity = accel.map(|value| value / magnitude);
+
+        Some(ImuCalibration {
+            accel_offset: [0, 1, 2].map(|axis| accel[axis] - gravity[axis]),
+            gyro_offset,
+            gravity,
+        })
+    }
+}
+
+fn length(vector: [f32; 3]) -> f32 {
+    libm::sqrtf(vector[0] * vector[0] + vector[1] * vector[1] + vector[2] * vector[2])
+}
+
+fn rotate(matrix: &[[f32; 3]; 3], vector: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+/// Rotation matrix that turns the unit vector `from` onto axis 0.
+fn rotation_to_up(from: [f32; 3]) -> [[f32; 3]; 3] {
+    // Rodrigues' formula, the rotation axis is perpendicular to axis 0 so its first term is 0
+    let [x, y, z] = from;
+    let cos = x;
+
+    if cos < -0.999 {
+        // Upside down, turn half a circle around axis 2
+        return [[-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0]];
+    }
+
+    let k = 1.0 / (1.0 + cos);
+    let (v1, v2) = (-z, y);
+
+    [
+        [cos, v2, -v1],
+        [-v2, 1.0 - v2 * v2 * k, v1 * v2 * k],
+        [v1, v1 * v2 * k, 1.0 - v1 * v1 * k],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: [f32; 3], expected: [f32; 3]) {
+        for axis in 0..3 {
+            assert!(
+                libm::fabsf(actual[axis] - expected[axis]) < 1e-4,
+                "{:?} isn't {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    fn normalize(vector: [f32; 3]) -> [f32; 3] {
+        let magnitude = length(vector);
+        vector.map(|value| value / magnitude)
+    }
+
+    fn resting(accel: [f32; 3], gyro: [f32; 3]) -> ImuSample {
+        ImuSample {
+            timestamp_ms: 0,
+            accel,
+            gyro,
+            temperature: 25.0,
+        }
+    }
+
+    const UP: [f32; 3] = [1.0, 0.0, 0.0];
+
+    #[test]
+    fn gravity_is_rotated_onto_axis_0() {
+        let directions = [
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, -1.0],
+            [0.6, -0.3, 0.5],
+            [-0.5, 0.7, 0.2],
+            [-0.998, 0.05, 0.0],
+        ];
+
+        for direction in directions.map(normalize) {
+            let rotation = rotation_to_up(direction);
+            assert_near(rotate(&rotation, direction), UP);
+
+            // Lengths stay the same, it only rotates
+            let other = [0.2, -0.4, 0.9];
+            let rotated = rotate(&rotation, other);
+            assert!(libm::fabsf(length(rotated) - length(other)) < 1e-4);
+        }
+    }
+
+    #[test]
+    fn upside_down_turns_half_a_circle() {
+        let rotation = rotation_to_up([-1.0, 0.0, 0.0]);
+        assert_near(rotate(&rotation, [-1.0, 0.0, 0.0]), UP);
+        assert_near(rotate(&rotation, [0.0, 0.0, 1.0]), [0.0, 0.0, 1.0]);
+
+        // Close enough to upside down takes the same branch
+        let almost = normalize([-1.0, 0.02, 0.0]);
+        let rotated = rotate(&rotation_to_up(almost), almost);
+        assert!(rotated[0] > 0.999, "{:?}", rotated);
+    }
+
+    #[test]
+    fn resting_sensor_is_calibrated() {
+        let tilted = normalize([0.8, 0.0, 0.6]);
+        let accel = [tilted[0] + 0.02, tilted[1] - 0.01, tilted[2]];
+        let gyro = [1.5, -0.5, 0.25];
+
+        let mut calibrator = Calibrator::new();
+        while !calibrator.is_done() {
+            calibrator.add(&resting(accel, gyro));
+        }
+
+        let calibration = calibrator.finish().unwrap();
+        assert_near(calibration.gyro_offset, gyro);
+
+        // The resting reading becomes level and still
+        let mut sample = resting(accel, gyro);
+        calibration.apply(&mut sample);
+        assert_near(sample.accel, UP);
+        assert_near(sample.gyro, [0.0; 3]);
+    }
+
+    #[test]
+    fn moving_while_sampling_fails() {
+        let mut calibrator = Calibrator::new();
+        assert!(calibrator.finish().is_none());
+
+        for index in 0..CALIBRATION_SAMPLES {
+            let bump = if index == CALIBRATION_SAMPLES / 2 {
+                0.2
+            } else {
+                0.0
+            };
+            calibrator.add(&resting([1.0 + bump, 0.0, 0.0], [0.0; 3]));
+        }
+
+        assert!(calibrator.is_done());
+        assert!(calibrator.finish().is_none());
+    }
+
+    #[test]
+    fn no_calibration_changes_nothing() {
+        let mut sample = resting([0.3, -0.2, 0.9], [10.0, 20.0, -30.0]);
+        ImuCalibration::NONE.apply(&mut sample);
+        assert_near(sample.accel, [0.3, -0.2, 0.9]);
+        assert_near(sample.gyro, [10.0, 20.0, -30.0]);
+    }
+}
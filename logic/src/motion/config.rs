@@ -0,0 +1,44 @@
+// Thresholds, accelerations in g and rotations in degrees per second
+
+/// Weight of a new sample in the gravity estimate, about a third of a second at 200 Hz.
+pub const GRAVITY_FILTER: f32 = 0.015;
+
+pub const MOTION_THRESHOLD: f32 = 0.08;
+pub const MOTION_GYRO_THRESHOLD: f32 = 20.0;
+/// Resting this long before moving counts as being picked up.
+pub const PICK_UP_REST_MS: u64 = 1_000;
+/// Moving this long after resting counts as being picked up, shorter is a tap.
+pub const PICK_UP_MOTION_MS: u64 = 250;
+/// Longest pause that still counts as continuous motion.
+pub const MOTION_GAP_MS: u64 = 100;
+/// Still this long after moving counts as being set down.
+pub const SET_DOWN_STILL_MS: u64 = 800;
+
+pub const TAP_THRESHOLD: f32 = 0.5;
+pub const TAP_MAX_DURATION_MS: u64 = 150;
+pub const DOUBLE_TAP_WINDOW_MS: u64 = 400;
+
+pub const SHAKE_THRESHOLD: f32 = 0.8;
+/// Number of peaks within the window that make a shake.
+pub const SHAKE_PEAKS: usize = 4;
+pub const SHAKE_WINDOW_MS: u64 = 1_200;
+
+pub const SPIN_RATE: f32 = 180.0;
+pub const SPIN_DURATION_MS: u64 = 400;
+
+/// Sideways gravity that counts as tilted, 0.5g is about 30 degrees.
+pub const TILT_THRESHOLD: f32 = 0.5;
+pub const UPSIDE_DOWN_THRESHOLD: f32 = 0.7;
+pub const ORIENTATION_HOLD_MS: u64 = 300;
+
+/// Time in seconds over which gravity corrects the gyro in the attitude estimate.
+pub const FILTER_TIME_CONSTANT: f32 = 2.5;
+/// Only correct with gravity when the measured acceleration is this close to 1g.
+pub const ACCEL_TRUST: f32 = 0.15;
+/// Fraction of the yaw that is let go every second, so the head recenters.
+pub const YAW_RECENTER: f32 = 0.3;
+
+/// Readings averaged by a calibration, 2 seconds at 200 Hz.
+pub const CALIBRATION_SAMPLES: usize = 400;
+/// Spread of the measured acceleration in g above which the owl wasn't resting.
+pub const CALIBRATION_MAX_DEVIATION: f32 = 0.05;
@@ -0,0 +1,515 @@
+use super::config::*;
+
+/// One reading of the MPU6050, accelerations in g and rotations in degrees per second.
+///
+/// Axis 0 points up, axis 1 left to right and axis 2 back to front.
+#[derive(Clone, Copy, Default)]
+pub struct ImuSample {
+    pub timestamp_ms: u64,
+    pub accel: [f32; 3],
+    pub gyro: [f32; 3],
+    pub temperature: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Gesture {
+    PickedUp,
+    SetDown,
+    Shake,
+    Tap,
+    DoubleTap,
+    TiltLeft,
+    TiltRight,
+    UpsideDown,
+    Spin,
+}
+
+impl Gesture {
+    pub const ALL: [Gesture; 9] = [
+        Gesture::PickedUp,
+        Gesture::SetDown,
+        Gesture::Shake,
+        Gesture::Tap,
+        Gesture::DoubleTap,
+        Gesture::TiltLeft,
+        Gesture::TiltRight,
+        Gesture::UpsideDown,
+        Gesture::Spin,
+    ];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Gesture::PickedUp => "picked up",
+            Gesture::SetDown => "set down",
+            Gesture::Shake => "shake",
+            Gesture::Tap => "tap",
+            Gesture::DoubleTap => "double tap",
+            Gesture::TiltLeft => "tilt left",
+            Gesture::TiltRight => "tilt right",
+            Gesture::UpsideDown => "upside down",
+            Gesture::Spin => "spin",
+        }
+    }
+
+    pub fn get_from_name(name: &str) -> Option<Gesture> {
+        Gesture::ALL
+            .into_iter()
+            .find(|gesture| gesture.get_name() == name)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Orientation {
+    Upright,
+    TiltLeft,
+    TiltRight,
+    UpsideDown,
+}
+
+/// Classifies gestures from a stream of IMU samples.
+pub struct GestureRecognizer {
+    gravity: [f32; 3],
+    initialized: bool,
+
+    // Resting or being moved around
+    moving: bool,
+    held: bool,
+    pick_up_pending: bool,
+    state_since_ms: u64,
+    last_motion_ms: u64,
+
+    // Tap detection
+    spike_start_ms: Option<u64>,
+    taps: u8,
+    last_tap_ms: u64,
+
+    // Shake detection
+    peaks: [u64; SHAKE_PEAKS],
+    peak_index: usize,
+    above_shake: bool,
+    last_shake_ms: Option<u64>,
+
+    // Spin detection
+    spin_start_ms: Option<u64>,
+    spin_reported: bool,
+
+    // Orientation detection
+    orientation: Orientation,
+    candidate: Orientation,
+    candidate_since_ms: u64,
+}
+
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GestureRecognizer {
+    pub const fn new() -> Self {
+        Self {
+            gravity: [1.0, 0.0, 0.0],
+            initialized: false,
+            moving: false,
+            held: false,
+            pick_up_pending: false,
+            state_since_ms: 0,
+            last_motion_ms: 0,
+            spike_start_ms: None,
+            taps: 0,
+            last_tap_ms: 0,
+            peaks: [0; SHAKE_PEAKS],
+            peak_index: 0,
+            above_shake: false,
+            last_shake_ms: None,
+            spin_start_ms: None,
+            spin_reported: false,
+            orientation: Orientation::Upright,
+            candidate: Orientation::Upright,
+            candidate_since_ms: 0,
+        }
+    }
+
+    /// Feed the next sample, returns a gesture when one was completed.
+    pub fn update(&mut self, sample: &ImuSample) -> Option<Gesture> {
+        let now = sample.timestamp_ms;
+
+        if !self.initialized {
+            self.gravity = sample.accel;
+            self.state_since_ms = now;
+            self.candidate_since_ms = now;
+            self.initialized = true;
+        }
+
+        // Low-pass the acceleration to follow the direction of gravity
+        for axis in 0..3 {
+            self.gravity[axis] += (sample.accel[axis] - self.gravity[axis]) * GRAVITY_FILTER;
+        }
+
+        let magnitude = libm::sqrtf(
+            sample.accel[0] * sample.accel[0]
+                + sample.accel[1] * sample.accel[1]
+                + sample.accel[2] * sample.accel[2],
+        );
+        // Acceleration on top of gravity
+        let dynamic = libm::fabsf(magnitude - 1.0);
+        let rotation = libm::sqrtf(
+            sample.gyro[0] * sample.gyro[0]
+                + sample.gyro[1] * sample.gyro[1]
+                + sample.gyro[2] * sample.gyro[2],
+        );
+
+        // Every detector runs on every sample, the first gesture found wins
+        let shake = self.detect_shake(now, dynamic);
+        let tap = self.detect_tap(now, dynamic);
+        let spin = self.detect_spin(now, sample.gyro[0]);
+        let rest = self.detect_rest(now, dynamic, rotation);
+        let orientation = self.detect_orientation(now);
+
+        shake.or(spin).or(orientation).or(rest).or(tap)
+    }
+
+    fn detect_shake(&mut self, now: u64, dynamic: f32) -> Option<Gesture> {
+        let above = dynamic > SHAKE_THRESHOLD;
+        let rising = above && !self.above_shake;
+        self.above_shake = above;
+
+        if !rising {
+            return None;
+        }
+
+        self.peaks[self.peak_index] = now;
+        self.peak_index = (self.peak_index + 1) % SHAKE_PEAKS;
+
+        // The oldest of the last peaks is still within the window
+        let oldest = self.peaks[self.peak_index];
+        if oldest != 0 && now - oldest <= SHAKE_WINDOW_MS {
+            self.peaks = [0; SHAKE_PEAKS];
+            // A shake is a lot of spikes, not taps
+            self.last_shake_ms = Some(now);
+            self.taps = 0;
+            self.spike_start_ms = None;
+            return Some(Gesture::Shake);
+        }
+
+        None
+    }
+
+    fn detect_tap(&mut self, now: u64, dynamic: f32) -> Option<Gesture> {
+        if dynamic > TAP_THRESHOLD {
+            // Spikes only count as taps when the owl isn't being held or shaken
+            let after_shake = self
+                .last_shake_ms
+                .is_some_and(|shake| now - shake <= SHAKE_WINDOW_MS);
+            if self.spike_start_ms.is_none() && !self.held && !after_shake {
+                self.spike_start_ms = Some(now);
+            }
+        } else if self
+            .spike_start_ms
+            .take()
+            .is_some_and(|start| now - start <= TAP_MAX_DURATION_MS)
+        {
+            self.taps = self.taps.saturating_add(1);
+            self.last_tap_ms = now;
+        }
+
+        // Wait until no more taps follow, more than two is knocking or shaking
+        if self.taps == 0 || now - self.last_tap_ms <= DOUBLE_TAP_WINDOW_MS {
+            return None;
+        }
+
+        let taps = core::mem::take(&mut self.taps);
+        match taps {
+            1 => Some(Gesture::Tap),
+            2 => Some(Gesture::DoubleTap),
+            _ => None,
+        }
+    }
+
+    fn detect_spin(&mut self, now: u64, vertical_rotation: f32) -> Option<Gesture> {
+        if libm::fabsf(vertical_rotation) < SPIN_RATE {
+            self.spin_start_ms = None;
+            self.spin_reported = false;
+            return None;
+        }
+
+        let start = *self.spin_start_ms.get_or_insert(now);
+        if !self.spin_reported && now - start >= SPIN_DURATION_MS {
+            self.spin_reported = true;
+            return Some(Gesture::Spin);
+        }
+
+        None
+    }
+
+    fn detect_rest(&mut self, now: u64, dynamic: f32, rotation: f32) -> Option<Gesture> {
+        if dynamic > MOTION_THRESHOLD || rotation > MOTION_GYRO_THRESHOLD {
+            self.last_motion_ms = now;
+
+            if !self.moving {
+                self.pick_up_pending = now - self.state_since_ms >= PICK_UP_REST_MS;
+                self.moving = true;
+                self.state_since_ms = now;
+            }
+
+            // Taps are short, being picked up keeps moving for a while
+            if self.pick_up_pending && now - self.state_since_ms >= PICK_UP_MOTION_MS {
+                self.pick_up_pending = false;
+                self.held = true;
+                return Some(Gesture::PickedUp);
+            }
+        } else if now - self.last_motion_ms > MOTION_GAP_MS {
+            // Motion has to be continuous to be picked up
+            self.pick_up_pending = false;
+        }
+
+        if !self.moving || now - self.last_motion_ms < SET_DOWN_STILL_MS {
+            return None;
+        }
+
+        self.moving = false;
+        self.state_since_ms = now;
+
+        if self.held && self.orientation == Orientation::Upright {
+            self.held = false;
+            return Some(Gesture::SetDown);
+        }
+
+        None
+    }
+
+    fn detect_orientation(&mut self, now: u64) -> Option<Gesture> {
+        let [vertical, sideways, _] = self.gravity;
+
+        let orientation = if vertical < -UPSIDE_DOWN_THRESHOLD {
+            Orientation::UpsideDown
+        } else if sideways > TILT_THRESHOLD {
+            Orientation::TiltRight
+        } else if sideways < -TILT_THRESHOLD {
+            Orientation::TiltLeft
+        } else {
+            Orientation::Upright
+        };
+
+        if orientation != self.candidate {
+            self.candidate = orientation;
+            self.candidate_since_ms = now;
+            return None;
+        }
+
+        if orientation == self.orientation || now - self.candidate_since_ms < ORIENTATION_HOLD_MS {
+            return None;
+        }
+
+        self.orientation = orientation;
+        match orientation {
+            Orientation::Upright => None,
+            Orientation::TiltLeft => Some(Gesture::TiltLeft),
+            Orientation::TiltRight => Some(Gesture::TiltRight),
+            Orientation::UpsideDown => Some(Gesture::UpsideDown),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use Gesture::*;
+
+    /// 200 Hz, like the sensor.
+    const PERIOD_MS: u64 = 5;
+    const UPRIGHT: [f32; 3] = [1.0, 0.0, 0.0];
+    const STILL: [f32; 3] = [0.0; 3];
+
+    /// Builds a timeline of samples piece by piece.
+    struct Trace {
+        samples: Vec<ImuSample>,
+        now: u64,
+    }
+
+    impl Trace {
+        fn new() -> Self {
+            Self {
+                samples: Vec::new(),
+                now: 0,
+            }
+        }
+
+        fn hold(mut self, duration_ms: u64, accel: [f32; 3], gyro: [f32; 3]) -> Self {
+            let end = self.now + duration_ms;
+            while self.now < end {
+                self.samples.push(ImuSample {
+                    timestamp_ms: self.now,
+                    accel,
+                    gyro,
+                    temperature: 25.0,
+                });
+                self.now += PERIOD_MS;
+            }
+            self
+        }
+
+        fn rest(self, duration_ms: u64) -> Self {
+            self.hold(duration_ms, UPRIGHT, STILL)
+        }
+
+        /// A short knock from above, `g` on top of gravity.
+        fn knock(self, g: f32) -> Self {
+            self.hold(2 * PERIOD_MS, [1.0 + g, 0.0, 0.0], STILL)
+        }
+
+        /// Carried around, swaying a little.
+        fn carry(mut self, duration_ms: u64) -> Self {
+            let end = self.now + duration_ms;
+            while self.now < end {
+                let sway = if (self.now / 100).is_multiple_of(2) {
+                    0.2
+                } else {
+                    -0.2
+                };
+                self = self.hold(PERIOD_MS, [1.0 + sway, 0.0, 0.0], [0.0, 40.0, 0.0]);
+            }
+            self
+        }
+
+        fn gestures(&self) -> Vec<Gesture> {
+            let mut recognizer = GestureRecognizer::new();
+            self.samples
+                .iter()
+                .filter_map(|sample| recognizer.update(sample))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn resting_is_nothing() {
+        assert_eq!(Trace::new().rest(10_000).gestures(), []);
+    }
+
+    #[test]
+    fn tap() {
+        let trace = Trace::new().rest(1_000).knock(0.7).rest(2_000);
+        assert_eq!(trace.gestures(), [Tap]);
+    }
+
+    #[test]
+    fn double_tap() {
+        let trace = Trace::new()
+            .rest(1_000)
+            .knock(0.7)
+            .rest(200)
+            .knock(0.7)
+            .rest(2_000);
+        assert_eq!(trace.gestures(), [DoubleTap]);
+    }
+
+    #[test]
+    fn knocking_is_no_tap() {
+        let mut trace = Trace::new().rest(1_000);
+        for _ in 0..4 {
+            trace = trace.knock(0.7).rest(200);
+        }
+        assert_eq!(trace.rest(2_000).gestures(), []);
+    }
+
+    #[test]
+    fn slow_taps_are_two_taps() {
+        let trace = Trace::new()
+            .rest(1_000)
+            .knock(0.7)
+            .rest(1_000)
+            .knock(0.7)
+            .rest(2_000);
+        assert_eq!(trace.gestures(), [Tap, Tap]);
+    }
+
+    #[test]
+    fn pick_up_and_set_down() {
+        let trace = Trace::new().rest(2_000).carry(3_000).rest(2_000);
+        assert_eq!(trace.gestures(), [PickedUp, SetDown]);
+    }
+
+    #[test]
+    fn moving_without_resting_first_is_no_pick_up() {
+        let trace = Trace::new().rest(500).carry(3_000).rest(2_000);
+        assert_eq!(trace.gestures(), []);
+    }
+
+    #[test]
+    fn knocks_while_held_are_no_taps() {
+        let trace = Trace::new()
+            .rest(2_000)
+            .carry(1_000)
+            .knock(0.7)
+            .carry(1_000)
+            .rest(2_000);
+        assert_eq!(trace.gestures(), [PickedUp, SetDown]);
+    }
+
+    #[test]
+    fn shake() {
+        let mut trace = Trace::new().rest(500);
+        for _ in 0..4 {
+            trace = trace
+                .hold(200, [2.0, 0.0, 0.0], STILL)
+                .hold(100, [0.6, 0.0, 0.0], STILL);
+        }
+        assert_eq!(trace.rest(2_000).gestures(), [Shake]);
+    }
+
+    #[test]
+    fn spin() {
+        let trace = Trace::new()
+            .rest(500)
+            .hold(1_000, UPRIGHT, [250.0, 0.0, 0.0])
+            .rest(2_000);
+        assert_eq!(trace.gestures(), [Spin]);
+    }
+
+    #[test]
+    fn slow_turn_is_no_spin() {
+        let trace = Trace::new()
+            .rest(500)
+            .hold(1_000, UPRIGHT, [90.0, 0.0, 0.0])
+            .rest(2_000);
+        assert_eq!(trace.gestures(), []);
+    }
+
+    #[test]
+    fn tilts() {
+        let trace = Trace::new()
+            .rest(500)
+            .hold(2_000, [0.8, 0.6, 0.0], STILL)
+            .rest(2_000)
+            .hold(2_000, [0.8, -0.6, 0.0], STILL)
+            .rest(2_000);
+        assert_eq!(trace.gestures(), [TiltRight, TiltLeft]);
+    }
+
+    #[test]
+    fn upside_down() {
+        let trace = Trace::new()
+            .rest(500)
+            .hold(2_000, [-1.0, 0.0, 0.0], STILL)
+            .rest(2_000);
+        assert_eq!(trace.gestures(), [UpsideDown]);
+    }
+
+    #[test]
+    fn short_tilt_is_ignored() {
+        let trace = Trace::new()
+            .rest(500)
+            .hold(400, [0.8, 0.6, 0.0], STILL)
+            .rest(2_000);
+        assert_eq!(trace.gestures(), []);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for gesture in Gesture::ALL {
+            assert_eq!(Gesture::get_from_name(gesture.get_name()), Some(gesture));
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod calibration;
+pub mod config;
+pub mod gesture;
+pub mod orientation;
+pub mod trace;
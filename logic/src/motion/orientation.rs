@@ -0,0 +1,198 @@
+use super::config::{ACCEL_TRUST, FILTER_TIME_CONSTANT, YAW_RECENTER};
+use super::gesture::ImuSample;
+
+/// Orientation of the owl in degrees.
+///
+/// Roll tilts to the side, pitch leans forward and yaw turns around the vertical axis.
+#[derive(Clone, Copy, Default)]
+pub struct Attitude {
+    pub roll: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+}
+
+impl Attitude {
+    pub const LEVEL: Attitude = Attitude {
+        roll: 0.0,
+        pitch: 0.0,
+        yaw: 0.0,
+    };
+}
+
+/// Complementary filter, integrates the gyro and corrects its drift with gravity.
+///
+/// Gravity says nothing about yaw, so it slowly returns to 0 instead.
+pub struct ComplementaryFilter {
+    attitude: Attitude,
+    last_timestamp_ms: Option<u64>,
+}
+
+impl Default for ComplementaryFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ComplementaryFilter {
+    pub const fn new() -> Self {
+        Self {
+            attitude: Attitude::LEVEL,
+            last_timestamp_ms: None,
+        }
+    }
+
+    pub fn update(&mut self, sample: &ImuSample) -> Attitude {
+        let [up, side, front] = sample.accel;
+        let accel_roll = libm::atan2f(side, up).to_degrees();
+        let accel_pitch = libm::atan2f(front, libm::sqrtf(up * up + side * side)).to_degrees();
+
+        let Some(last) = self.last_timestamp_ms.replace(sample.timestamp_ms) else {
+            // Start from gravity instead of level
+            self.attitude.roll = accel_roll;
+            self.attitude.pitch = accel_pitch;
+            return self.attitude;
+        };
+        let dt = (sample.timestamp_ms.saturating_sub(last)) as f32 / 1000.0;
+
+        self.attitude.roll = wrap(self.attitude.roll + sample.gyro[2] * dt);
+        self.attitude.pitch = wrap(self.attitude.pitch + sample.gyro[1] * dt);
+        self.attitude.yaw = wrap(self.attitude.yaw + sample.gyro[0] * dt);
+        self.attitude.yaw -= self.attitude.yaw * (YAW_RECENTER * dt).min(1.0);
+
+        // Gravity is only a good reference while nothing else is pushing the owl around
+        let magnitude = libm::sqrtf(up * up + side * side + front * front);
+        if libm::fabsf(magnitude - 1.0) < ACCEL_TRUST {
+            let correction = dt / (FILTER_TIME_CONSTANT + dt);
+            self.attitude.roll =
+                wrap(self.attitude.roll + wrap(accel_roll - self.attitude.roll) * correction);
+            self.attitude.pitch =
+                wrap(self.attitude.pitch + wrap(accel_pitch - self.attitude.pitch) * correction);
+        }
+
+        self.attitude
+    }
+}
+
+/// Wrap an angle into -180..180 degrees.
+fn wrap(angle: f32) -> f32 {
+    let wrapped = libm::fmodf(angle + 180.0, 360.0);
+    if wrapped < 0.0 {
+        wrapped + 180.0
+    } else {
+        wrapped - 180.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP_MS: u64 = 10;
+
+    /// Feed the same reading every 10 ms for a while, returns the last attitude.
+    fn run(
+        filter: &mut ComplementaryFilter,
+        start_ms: u64,
+        duration_ms: u64,
+        accel: [f32; 3],
+        gyro: [f32; 3],
+    ) -> Attitude {
+        let mut attitude = Attitude::LEVEL;
+        for timestamp_ms in (start_ms..=start_ms + duration_ms).step_by(STEP_MS as usize) {
+            attitude = filter.update(&ImuSample {
+                timestamp_ms,
+                accel,
+                gyro,
+                temperature: 25.0,
+            });
+        }
+        attitude
+    }
+
+    const LEVEL: [f32; 3] = [1.0, 0.0, 0.0];
+    /// Too far from 1g to be trusted, only the gyro counts.
+    const FALLING: [f32; 3] = [0.0, 0.0, 0.0];
+    const STILL: [f32; 3] = [0.0, 0.0, 0.0];
+
+    fn assert_near(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            libm::fabsf(actual - expected) <= tolerance,
+            "{} isn't {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn converges_to_gravity() {
+        let (roll, pitch) = (30f32.to_radians(), -20f32.to_radians());
+        let tilted = [
+            libm::cosf(roll) * libm::cosf(pitch),
+            libm::sinf(roll) * libm::cosf(pitch),
+            libm::sinf(pitch),
+        ];
+
+        let mut filter = ComplementaryFilter::new();
+        run(&mut filter, 0, 0, LEVEL, STILL);
+        let attitude = run(&mut filter, STEP_MS, 20_000, tilted, STILL);
+
+        assert_near(attitude.roll, 30.0, 0.1);
+        assert_near(attitude.pitch, -20.0, 0.1);
+        assert_near(attitude.yaw, 0.0, 0.01);
+    }
+
+    #[test]
+    fn gyro_integrates_on_its_own_axis() {
+        let mut filter = ComplementaryFilter::new();
+        let attitude = run(&mut filter, 0, 1_000, FALLING, [0.0, 0.0, 45.0]);
+        assert_near(attitude.roll, 45.0, 0.01);
+        assert_near(attitude.pitch, 0.0, 0.01);
+
+        let mut filter = ComplementaryFilter::new();
+        let attitude = run(&mut filter, 0, 1_000, FALLING, [0.0, -45.0, 0.0]);
+        assert_near(attitude.roll, 0.0, 0.01);
+        assert_near(attitude.pitch, -45.0, 0.01);
+
+        // Yaw is already recentering while it turns
+        let mut filter = ComplementaryFilter::new();
+        let attitude = run(&mut filter, 0, 1_000, FALLING, [45.0, 0.0, 0.0]);
+        assert!(
+            attitude.yaw > 30.0 && attitude.yaw < 45.0,
+            "{}",
+            attitude.yaw
+        );
+        assert_near(attitude.roll, 0.0, 0.01);
+    }
+
+    #[test]
+    fn turning_past_180_wraps_around() {
+        let mut filter = ComplementaryFilter::new();
+        let attitude = run(&mut filter, 0, 2_500, FALLING, [0.0, 0.0, 100.0]);
+        assert_near(attitude.roll, 250.0 - 360.0, 0.01);
+    }
+
+    #[test]
+    fn wrap_keeps_angles_in_range() {
+        assert_eq!(wrap(0.0), 0.0);
+        assert_eq!(wrap(45.0), 45.0);
+        assert_eq!(wrap(180.0), -180.0);
+        assert_eq!(wrap(-180.0), -180.0);
+        assert_eq!(wrap(190.0), -170.0);
+        assert_eq!(wrap(-190.0), 170.0);
+        assert_eq!(wrap(540.0), -180.0);
+        assert_eq!(wrap(-370.0), -10.0);
+    }
+
+    #[test]
+    fn yaw_recenters() {
+        let mut filter = ComplementaryFilter::new();
+        let turned = run(&mut filter, 0, 1_000, LEVEL, [60.0, 0.0, 0.0]);
+        assert!(turned.yaw > 30.0);
+
+        let half = run(&mut filter, 1_000 + STEP_MS, 1_000, LEVEL, STILL);
+        assert!(half.yaw > 0.0 && half.yaw < turned.yaw);
+
+        let settled = run(&mut filter, 2_000 + STEP_MS, 30_000, LEVEL, STILL);
+        assert_near(settled.yaw, 0.0, 0.01);
+    }
+}
@@ -0,0 +1,150 @@
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use super::gesture::{Gesture, GestureRecognizer, ImuSample};
+use super::orientation::Attitude;
+
+/// Columns of an IMU trace, one sample per line.
+///
+/// The sample columns are the calibrated readings the gesture recognizer sees, so recorded
+/// traces can be replayed through it.
+pub const TRACE_HEADER: &str = "timestamp_ms,accel_x,accel_y,accel_z,gyro_x,gyro_y,gyro_z,\
+    temperature,roll,pitch,yaw,gesture";
+
+pub fn write_trace_line(
+    out: &mut impl Write,
+    sample: &ImuSample,
+    attitude: &Attitude,
+    gesture: Option<Gesture>,
+) -> fmt::Result {
+    let [accel_x, accel_y, accel_z] = sample.accel;
+    let [gyro_x, gyro_y, gyro_z] = sample.gyro;
+
+    writeln!(
+        out,
+        "{},{:.4},{:.4},{:.4},{:.2},{:.2},{:.2},{:.1},{:.1},{:.1},{:.1},{}",
+        sample.timestamp_ms,
+        accel_x,
+        accel_y,
+        accel_z,
+        gyro_x,
+        gyro_y,
+        gyro_z,
+        sample.temperature,
+        attitude.roll,
+        attitude.pitch,
+        attitude.yaw,
+        gesture.map(|gesture| gesture.get_name()).unwrap_or(""),
+    )
+}
+
+/// Parse a trace line into the sample and the gesture detected when it was recorded.
+///
+/// Returns `None` for the header and malformed lines.
+pub fn parse_trace_line(line: &str) -> Option<(ImuSample, Option<Gesture>)> {
+    let mut columns = line.trim_end().split(',');
+    let mut number = || columns.next()?.parse::<f32>().ok();
+
+    let timestamp_ms = number()? as u64;
+    let accel = [number()?, number()?, number()?];
+    let gyro = [number()?, number()?, number()?];
+    let temperature = number()?;
+
+    // Attitude isn't needed to replay
+    for _ in 0..3 {
+        number()?;
+    }
+
+    let gesture = columns.next().and_then(Gesture::get_from_name);
+
+    let sample = ImuSample {
+        timestamp_ms,
+        accel,
+        gyro,
+        temperature,
+    };
+
+    Some((sample, gesture))
+}
+
+/// Run a recorded trace through the gesture recognizer.
+///
+/// Returns the detected gestures with the timestamp of the sample that completed them.
+pub fn replay(trace: &str) -> Vec<(u64, Gesture)> {
+    let mut recognizer = GestureRecognizer::new();
+
+    trace
+        .lines()
+        .filter_map(parse_trace_line)
+        .filter_map(|(sample, _)| {
+            let gesture = recognizer.update(&sample)?;
+            Some((sample.timestamp_ms, gesture))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::String;
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Tapped, picked up, shaken and set down again, at the full sample rate.
+    const HANDLING: &str = include_str!("traces/handling.csv");
+
+    #[test]
+    fn replay_finds_the_recorded_gestures() {
+        assert_eq!(HANDLING.lines().next(), Some(TRACE_HEADER));
+
+        let recorded: Vec<(u64, Gesture)> = HANDLING
+            .lines()
+            .filter_map(parse_trace_line)
+            .filter_map(|(sample, gesture)| Some((sample.timestamp_ms, gesture?)))
+            .collect();
+        let gestures: Vec<Gesture> = recorded.iter().map(|(_, gesture)| *gesture).collect();
+
+        assert_eq!(
+            gestures,
+            [
+                Gesture::Tap,
+                Gesture::PickedUp,
+                Gesture::Shake,
+                Gesture::SetDown
+            ]
+        );
+        assert_eq!(replay(HANDLING), recorded);
+    }
+
+    #[test]
+    fn lines_round_trip() {
+        let sample = ImuSample {
+            timestamp_ms: 1_234,
+            accel: [0.9875, -0.0125, 0.5],
+            gyro: [12.25, -3.5, 0.0],
+            temperature: 26.5,
+        };
+        let attitude = Attitude {
+            roll: 1.5,
+            pitch: -2.5,
+            yaw: 0.0,
+        };
+
+        let mut line = String::new();
+        write_trace_line(&mut line, &sample, &attitude, Some(Gesture::DoubleTap)).unwrap();
+        let (parsed, gesture) = parse_trace_line(&line).unwrap();
+
+        assert_eq!(parsed.timestamp_ms, sample.timestamp_ms);
+        assert_eq!(parsed.accel, sample.accel);
+        assert_eq!(parsed.gyro, sample.gyro);
+        assert_eq!(parsed.temperature, sample.temperature);
+        assert_eq!(gesture, Some(Gesture::DoubleTap));
+    }
+
+    #[test]
+    fn header_and_malformed_lines_are_skipped() {
+        assert!(parse_trace_line(TRACE_HEADER).is_none());
+        assert!(parse_trace_line("").is_none());
+        assert!(parse_trace_line("1,2,3").is_none());
+    }
+}
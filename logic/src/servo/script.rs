@@ -0,0 +1,235 @@
+use alloc::{vec, vec::Vec};
+
+/// What a script needs to change in the frames it composes.
+pub trait ScriptFrame: Clone {
+    /// Swap left and right, the wings trade places and the neck turns the other way.
+    fn mirror(&mut self);
+    /// Scale how far the servos move away from their default position, in percent.
+    fn scale_amplitude(&mut self, percent: u16);
+    /// Take every keyframe `other` has, keep the rest.
+    fn merge(&mut self, other: &Self);
+}
+
+/// Composes animations out of smaller clips.
+///
+/// A script is compiled into a flat animation right before it is played, so random choices
+/// are made again every time.
+pub enum Script<F: 'static> {
+    /// Play the frames as they are.
+    Clip(&'static [Option<F>]),
+    /// Play the scripts one after another.
+    Sequence(&'static [Script<F>]),
+    /// Play the script a number of times.
+    Repeat(u8, &'static Script<F>),
+    /// Play one of the scripts, picked at random.
+    Choice(&'static [Script<F>]),
+    /// Swap left and right, see [`ScriptFrame::mirror`].
+    Mirror(&'static Script<F>),
+    /// Stretch the script in time, 200 plays it at half speed.
+    TimeScale(u16, &'static Script<F>),
+    /// Scale how far the servos move away from their default position, in percent.
+    AmplitudeScale(u16, &'static Script<F>),
+}
+
+impl<F: ScriptFrame> Script<F> {
+    /// Flatten the script, `pick(count)` picks a choice below `count`.
+    pub fn compile(&self, pick: &mut impl FnMut(usize) -> usize) -> Vec<Option<F>> {
+        let mut frames = Vec::new();
+        self.compile_into(&mut frames, pick);
+        frames
+    }
+
+    fn compile_into(&self, frames: &mut Vec<Option<F>>, pick: &mut impl FnMut(usize) -> usize) {
+        match self {
+            Script::Clip(animation) => frames.extend_from_slice(animation),
+            Script::Sequence(scripts) => {
+                for script in scripts.iter() {
+                    script.compile_into(frames, pick);
+                }
+            }
+            Script::Repeat(count, script) => {
+                // Compile every repetition so random choices differ between them
+                for _ in 0..*count {
+                    script.compile_into(frames, pick);
+                }
+            }
+            Script::Choice(scripts) => {
+                if scripts.is_empty() {
+                    return;
+                }
+                let index = pick(scripts.len()) % scripts.len();
+                scripts[index].compile_into(frames, pick);
+            }
+            Script::Mirror(script) => {
+                let start = frames.len();
+                script.compile_into(frames, pick);
+                frames[start..]
+                    .iter_mut()
+                    .flatten()
+                    .for_each(ScriptFrame::mirror);
+            }
+            Script::TimeScale(percent, script) => {
+                let scaled = time_scale(&script.compile(pick), *percent);
+                frames.extend(scaled);
+            }
+            Script::AmplitudeScale(percent, script) => {
+                let start = frames.len();
+                script.compile_into(frames, pick);
+                for frame in frames[start..].iter_mut().flatten() {
+                    frame.scale_amplitude(*percent);
+                }
+            }
+        }
+    }
+}
+
+fn time_scale<F: ScriptFrame>(animation: &[Option<F>], percent: u16) -> Vec<Option<F>> {
+    if animation.is_empty() {
+        return Vec::new();
+    }
+
+    let scale = |index: usize| (index * percent as usize + 50) / 100;
+    let mut frames: Vec<Option<F>> = vec![None; scale(animation.len() - 1) + 1];
+
+    // Keyframes that land on the same frame are merged, later ones win
+    for (index, frame) in animation.iter().enumerate() {
+        let Some(frame) = frame else {
+            continue;
+        };
+
+        match &mut frames[scale(index)] {
+            Some(existing) => existing.merge(frame),
+            slot => *slot = Some(frame.clone()),
+        }
+    }
+
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    const CENTER: i16 = 500;
+
+    /// A wing on each side and a neck turning around the center.
+    #[derive(Clone, Debug, PartialEq)]
+    struct Pose {
+        left: Option<i16>,
+        right: Option<i16>,
+        neck: Option<i16>,
+    }
+
+    impl ScriptFrame for Pose {
+        fn mirror(&mut self) {
+            core::mem::swap(&mut self.left, &mut self.right);
+            self.neck = self.neck.map(|neck| 2 * CENTER - neck);
+        }
+
+        fn scale_amplitude(&mut self, percent: u16) {
+            let scale = |value: i16| CENTER + (value - CENTER) * percent as i16 / 100;
+            self.left = self.left.map(scale);
+            self.right = self.right.map(scale);
+            self.neck = self.neck.map(scale);
+        }
+
+        fn merge(&mut self, other: &Self) {
+            self.left = other.left.or(self.left);
+            self.right = other.right.or(self.right);
+            self.neck = other.neck.or(self.neck);
+        }
+    }
+
+    const fn pose(left: i16, right: i16, neck: i16) -> Option<Pose> {
+        Some(Pose {
+            left: Some(left),
+            right: Some(right),
+            neck: Some(neck),
+        })
+    }
+
+    const fn neck(neck: i16) -> Option<Pose> {
+        Some(Pose {
+            left: None,
+            right: None,
+            neck: Some(neck),
+        })
+    }
+
+    const UP: &[Option<Pose>] = &[pose(100, 900, 300)];
+    const DOWN: &[Option<Pose>] = &[pose(900, 100, 700)];
+    const TURN: &[Option<Pose>] = &[neck(200), None, neck(800)];
+
+    fn compile(script: &Script<Pose>) -> Vec<Option<Pose>> {
+        script.compile(&mut |_| 0)
+    }
+
+    #[test]
+    fn sequences_play_in_order() {
+        static SCRIPT: Script<Pose> = Script::Sequence(&[Script::Clip(UP), Script::Clip(TURN)]);
+        assert_eq!(compile(&SCRIPT), [UP, TURN].concat());
+    }
+
+    #[test]
+    fn repeats_play_again() {
+        static SCRIPT: Script<Pose> = Script::Repeat(3, &Script::Clip(UP));
+        assert_eq!(compile(&SCRIPT), [UP, UP, UP].concat());
+
+        static NEVER: Script<Pose> = Script::Repeat(0, &Script::Clip(UP));
+        assert!(compile(&NEVER).is_empty());
+    }
+
+    #[test]
+    fn choices_are_picked_again_every_repeat() {
+        static SCRIPT: Script<Pose> =
+            Script::Repeat(3, &Script::Choice(&[Script::Clip(UP), Script::Clip(DOWN)]));
+
+        let mut picks = [1, 0, 1].into_iter();
+        let frames = SCRIPT.compile(&mut |count| {
+            assert_eq!(count, 2);
+            picks.next().unwrap()
+        });
+        assert_eq!(frames, [DOWN, UP, DOWN].concat());
+
+        static NOTHING: Script<Pose> = Script::Choice(&[]);
+        assert!(compile(&NOTHING).is_empty());
+    }
+
+    #[test]
+    fn mirror_only_changes_its_own_frames() {
+        static SCRIPT: Script<Pose> =
+            Script::Sequence(&[Script::Clip(UP), Script::Mirror(&Script::Clip(UP))]);
+        assert_eq!(compile(&SCRIPT), [UP, DOWN].concat());
+
+        // Mirrored twice is the original
+        static TWICE: Script<Pose> = Script::Mirror(&Script::Mirror(&Script::Clip(TURN)));
+        assert_eq!(compile(&TWICE), TURN);
+    }
+
+    #[test]
+    fn time_scale_stretches_and_merges() {
+        static SLOW: Script<Pose> = Script::TimeScale(200, &Script::Clip(TURN));
+        assert_eq!(compile(&SLOW), [neck(200), None, None, None, neck(800)]);
+
+        // Both keyframes land on the first frame, the later one wins
+        static FAST: Script<Pose> = Script::TimeScale(
+            25,
+            &Script::Sequence(&[Script::Clip(UP), Script::Clip(&[neck(650)])]),
+        );
+        assert_eq!(compile(&FAST), [pose(100, 900, 650)]);
+
+        static EMPTY: Script<Pose> = Script::TimeScale(200, &Script::Clip(&[]));
+        assert!(compile(&EMPTY).is_empty());
+    }
+
+    #[test]
+    fn amplitude_scale_moves_towards_the_center() {
+        static SCRIPT: Script<Pose> = Script::Sequence(&[
+            Script::AmplitudeScale(50, &Script::Clip(UP)),
+            Script::Clip(UP),
+        ]);
+        assert_eq!(compile(&SCRIPT), [pose(300, 700, 400), pose(100, 900, 300)]);
+    }
+}
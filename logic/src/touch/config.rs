@@ -0,0 +1,31 @@
+use super::detector::TouchPad;
+
+/// Readings used to find the first baseline.
+pub const BASELINE_SAMPLES: u32 = 16;
+/// Weight of a new untouched reading in the baseline, follows temperature and humidity.
+pub const BASELINE_FILTER: f32 = 0.002;
+/// Readings below the baseline are followed faster, a pad can't read less than untouched.
+pub const BASELINE_FILTER_DOWN: f32 = 0.05;
+/// Touched this long is taken as drift, the baseline starts again from the reading.
+pub const BASELINE_RESET_MS: u64 = 20_000;
+
+/// Fraction of the threshold the reading has to fall under to be released.
+pub const RELEASE_RATIO: f32 = 0.6;
+/// Time a reading has to stay over or under the threshold to count.
+pub const DEBOUNCE_MS: u64 = 40;
+pub const LONG_TOUCH_MS: u64 = 1_000;
+/// Longest time between touching the two head pads that still counts as a stroke.
+pub const STROKE_WINDOW_MS: u64 = 600;
+/// Longest time between touching the two wings that still counts as a hug.
+pub const HUG_WINDOW_MS: u64 = 300;
+
+/// Increase over the baseline that counts as touched, as a fraction of the baseline.
+pub fn pad_threshold(pad: TouchPad) -> f32 {
+    match pad {
+        TouchPad::HeadFront => 0.02,
+        TouchPad::HeadBack => 0.02,
+        // The wings are further from the electronics, their wires pick up more noise
+        TouchPad::WingLeft => 0.04,
+        TouchPad::WingRight => 0.04,
+    }
+}
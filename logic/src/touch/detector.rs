@@ -0,0 +1,394 @@
+use alloc::vec::Vec;
+
+use super::config::*;
+
+pub const PAD_COUNT: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TouchPad {
+    HeadFront = 0,
+    HeadBack = 1,
+    WingLeft = 2,
+    WingRight = 3,
+}
+
+impl TouchPad {
+    pub const ALL: [TouchPad; PAD_COUNT] = [
+        TouchPad::HeadFront,
+        TouchPad::HeadBack,
+        TouchPad::WingLeft,
+        TouchPad::WingRight,
+    ];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            TouchPad::HeadFront => "head front",
+            TouchPad::HeadBack => "head back",
+            TouchPad::WingLeft => "left wing",
+            TouchPad::WingRight => "right wing",
+        }
+    }
+
+    /// Stroking goes over both head pads.
+    fn is_head(&self) -> bool {
+        matches!(self, TouchPad::HeadFront | TouchPad::HeadBack)
+    }
+
+    fn is_wing(&self) -> bool {
+        matches!(self, TouchPad::WingLeft | TouchPad::WingRight)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TouchEvent {
+    /// Touched and let go.
+    Touch(TouchPad),
+    /// Still held after [`LONG_TOUCH_MS`].
+    LongTouch(TouchPad),
+    /// Both head pads touched one after the other, either way.
+    Stroke,
+    /// Both wings touched at once.
+    Hug,
+}
+
+impl TouchEvent {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            TouchEvent::Touch(_) => "touch",
+            TouchEvent::LongTouch(_) => "long touch",
+            TouchEvent::Stroke => "stroke",
+            TouchEvent::Hug => "hug",
+        }
+    }
+
+    pub fn get_pad(&self) -> Option<TouchPad> {
+        match self {
+            TouchEvent::Touch(pad) | TouchEvent::LongTouch(pad) => Some(*pad),
+            TouchEvent::Stroke | TouchEvent::Hug => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PadState {
+    /// Reading of the untouched pad.
+    baseline: f32,
+    threshold: f32,
+    touched: bool,
+    /// The reading crossed the threshold, the state changes once it stays there.
+    crossed_since_ms: Option<u64>,
+    touched_since_ms: u64,
+    long_reported: bool,
+    /// Releasing a pad that was part of a stroke or a hug isn't a touch.
+    in_combo: bool,
+}
+
+/// Turns raw pad readings into touch events.
+pub struct TouchDetector {
+    pads: [PadState; PAD_COUNT],
+    baseline_samples: u32,
+    last_head_press: Option<(TouchPad, u64)>,
+    last_wing_press: Option<(TouchPad, u64)>,
+    /// Head touches wait a moment, they might turn out to be the start of a stroke.
+    pending_touches: Vec<(TouchPad, u64)>,
+}
+
+impl Default for TouchDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TouchDetector {
+    pub fn new() -> Self {
+        Self {
+            pads: TouchPad::ALL.map(|pad| PadState {
+                baseline: 0.0,
+                threshold: pad_threshold(pad),
+                touched: false,
+                crossed_since_ms: None,
+                touched_since_ms: 0,
+                long_reported: false,
+                in_combo: false,
+            }),
+            baseline_samples: 0,
+            last_head_press: None,
+            last_wing_press: None,
+            pending_touches: Vec::new(),
+        }
+    }
+
+    /// Feed the next readings, in the order of [`TouchPad::ALL`]. Returns the events that were
+    /// completed, oldest first.
+    pub fn update(&mut self, now: u64, readings: &[u32; PAD_COUNT]) -> Vec<TouchEvent> {
+        // The first readings only find the baseline, nobody should be touching the owl yet
+        if self.baseline_samples < BASELINE_SAMPLES {
+            self.baseline_samples += 1;
+            for (state, reading) in self.pads.iter_mut().zip(readings) {
+                state.baseline += (*reading as f32 - state.baseline) / self.baseline_samples as f32;
+            }
+            return Vec::new();
+        }
+
+        // Waiting touches were let go before anything this reading finds
+        let mut events = self.take_pending_touches(now);
+        for pad in TouchPad::ALL {
+            events.extend(self.update_pad(now, pad, readings[pad as usize] as f32));
+        }
+        events
+    }
+
+    fn update_pad(&mut self, now: u64, pad: TouchPad, reading: f32) -> Option<TouchEvent> {
+        let state = &mut self.pads[pad as usize];
+        let increase = (reading - state.baseline) / state.baseline.max(1.0);
+
+        // Releasing takes less, so a reading around the threshold doesn't flicker
+        let over = if state.touched {
+            increase > state.threshold * RELEASE_RATIO
+        } else {
+            increase > state.threshold
+        };
+
+        // Follow slow drift while nobody touches the pad
+        if !state.touched && !over {
+            let filter = if reading < state.baseline {
+                BASELINE_FILTER_DOWN
+            } else {
+                BASELINE_FILTER
+            };
+            state.baseline += (reading - state.baseline) * filter;
+        }
+
+        if over != state.touched {
+            let since = *state.crossed_since_ms.get_or_insert(now);
+            if now - since < DEBOUNCE_MS {
+                return None;
+            }
+
+            state.crossed_since_ms = None;
+            state.touched = over;
+            return if over {
+                self.pressed(now, pad)
+            } else {
+                self.released(now, pad)
+            };
+        }
+        state.crossed_since_ms = None;
+
+        if !state.touched {
+            return None;
+        }
+
+        let held = now - state.touched_since_ms;
+        if held >= BASELINE_RESET_MS {
+            // Nobody holds on this long, the baseline drifted or something leans on the pad
+            state.baseline = reading;
+            state.touched = false;
+            return None;
+        }
+
+        if !state.long_reported && !state.in_combo && held >= LONG_TOUCH_MS {
+            state.long_reported = true;
+            return Some(TouchEvent::LongTouch(pad));
+        }
+
+        None
+    }
+
+    fn pressed(&mut self, now: u64, pad: TouchPad) -> Option<TouchEvent> {
+        let state = &mut self.pads[pad as usize];
+        state.touched_since_ms = now;
+        state.long_reported = false;
+        state.in_combo = false;
+
+        if pad.is_wing() {
+            return self.wing_pressed(now, pad);
+        }
+
+        match self.last_head_press.replace((pad, now)) {
+            Some((other, pressed_ms)) if other != pad && now - pressed_ms <= STROKE_WINDOW_MS => {
+                self.pads[pad as usize].in_combo = true;
+                self.pads[other as usize].in_combo = true;
+                self.last_head_press = None;
+                self.pending_touches.clear();
+                Some(TouchEvent::Stroke)
+            }
+            _ => None,
+        }
+    }
+
+    fn wing_pressed(&mut self, now: u64, pad: TouchPad) -> Option<TouchEvent> {
+        match self.last_wing_press.replace((pad, now)) {
+            Some((other, pressed_ms))
+                if other != pad
+                    && self.pads[other as usize].touched
+                    && now - pressed_ms <= HUG_WINDOW_MS =>
+            {
+                self.pads[pad as usize].in_combo = true;
+                self.pads[other as usize].in_combo = true;
+                self.last_wing_press = None;
+                Some(TouchEvent::Hug)
+            }
+            _ => None,
+        }
+    }
+
+    fn released(&mut self, now: u64, pad: TouchPad) -> Option<TouchEvent> {
+        let state = self.pads[pad as usize];
+        if state.in_combo || state.long_reported {
+            return None;
+        }
+
+        if pad.is_head() {
+            self.pending_touches.push((pad, now));
+            return None;
+        }
+
+        Some(TouchEvent::Touch(pad))
+    }
+
+    /// Touches no stroke can follow anymore.
+    fn take_pending_touches(&mut self, now: u64) -> Vec<TouchEvent> {
+        // Released in order, so the ones that are done are at the front
+        let done = self
+            .pending_touches
+            .iter()
+            .take_while(|(_, released_ms)| now - released_ms > STROKE_WINDOW_MS)
+            .count();
+
+        self.pending_touches
+            .drain(..done)
+            .map(|(pad, _)| TouchEvent::Touch(pad))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use TouchPad::*;
+
+    /// Same as the touch task.
+    const PERIOD_MS: u64 = 20;
+    const UNTOUCHED: u32 = 1_000;
+    /// 10% over the baseline, over the threshold of every pad.
+    const TOUCHED: u32 = 1_100;
+
+    /// Run readings through a detector, `touched` says which pads are touched at a time.
+    fn replay(duration_ms: u64, touched: impl Fn(u64, TouchPad) -> bool) -> Vec<TouchEvent> {
+        let mut detector = TouchDetector::new();
+        let mut now = 0;
+
+        // Nobody touches the owl while the baseline is found
+        for _ in 0..BASELINE_SAMPLES {
+            detector.update(now, &[UNTOUCHED; PAD_COUNT]);
+            now += PERIOD_MS;
+        }
+
+        let start = now;
+        let mut events = Vec::new();
+        while now < start + duration_ms {
+            let readings = TouchPad::ALL.map(|pad| {
+                if touched(now - start, pad) {
+                    TOUCHED
+                } else {
+                    UNTOUCHED
+                }
+            });
+            events.extend(detector.update(now, &readings));
+            now += PERIOD_MS;
+        }
+        events
+    }
+
+    #[test]
+    fn touch() {
+        let events = replay(2_000, |t, pad| pad == WingLeft && (100..300).contains(&t));
+        assert!(events == [TouchEvent::Touch(WingLeft)]);
+    }
+
+    #[test]
+    fn head_touch_waits_for_a_stroke() {
+        let events = replay(2_000, |t, pad| pad == HeadFront && (100..300).contains(&t));
+        assert!(events == [TouchEvent::Touch(HeadFront)]);
+    }
+
+    #[test]
+    fn long_touch() {
+        let events = replay(3_000, |t, pad| {
+            pad == WingRight && (100..2_000).contains(&t)
+        });
+        assert!(events == [TouchEvent::LongTouch(WingRight)]);
+    }
+
+    #[test]
+    fn stroke() {
+        let events = replay(2_000, |t, pad| match pad {
+            HeadFront => (100..400).contains(&t),
+            HeadBack => (300..600).contains(&t),
+            _ => false,
+        });
+        assert!(events == [TouchEvent::Stroke]);
+    }
+
+    #[test]
+    fn hug() {
+        // Held long enough for a long touch, which a hug doesn't send
+        let events = replay(3_000, |t, pad| match pad {
+            WingLeft => (100..2_000).contains(&t),
+            WingRight => (200..2_000).contains(&t),
+            _ => false,
+        });
+        assert!(events == [TouchEvent::Hug]);
+    }
+
+    #[test]
+    fn wings_one_after_the_other_are_no_hug() {
+        let events = replay(3_000, |t, pad| match pad {
+            WingLeft => (100..300).contains(&t),
+            WingRight => (400..600).contains(&t),
+            _ => false,
+        });
+        assert!(events == [TouchEvent::Touch(WingLeft), TouchEvent::Touch(WingRight)]);
+    }
+
+    #[test]
+    fn head_touches_wait_together() {
+        let events = replay(3_000, |t, pad| match pad {
+            // Too slow for a stroke, let go while the first touch still waits for one
+            HeadFront => (100..700).contains(&t),
+            HeadBack => (800..900).contains(&t),
+            _ => false,
+        });
+        assert!(events == [TouchEvent::Touch(HeadFront), TouchEvent::Touch(HeadBack)]);
+    }
+
+    #[test]
+    fn overlapping_pads_keep_every_event() {
+        let events = replay(4_000, |t, pad| match pad {
+            // A long touch while a head pad is tapped twice
+            WingRight => (100..2_000).contains(&t),
+            HeadFront => (200..300).contains(&t) || (1_000..1_100).contains(&t),
+            // Let go in the same reading as the long touch is sent
+            WingLeft => (500..1_100).contains(&t),
+            HeadBack => false,
+        });
+        assert!(
+            events
+                == [
+                    TouchEvent::Touch(HeadFront),
+                    TouchEvent::Touch(WingLeft),
+                    TouchEvent::LongTouch(WingRight),
+                    TouchEvent::Touch(HeadFront),
+                ]
+        );
+    }
+
+    #[test]
+    fn noise_is_no_touch() {
+        let events = replay(2_000, |t, pad| pad == WingLeft && (100..120).contains(&t));
+        assert!(events.is_empty());
+    }
+}
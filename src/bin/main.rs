@@ -11,24 +11,39 @@ use defmt::{error, info};
 use embassy_executor::Spawner;
 use esp_alloc::HeapStats;
 use esp_hal::clock::CpuClock;
+use esp_hal::rmt::Rmt;
+use esp_hal::rtc_cntl::Rtc;
+use esp_hal::system::software_reset;
 use esp_hal::timer::timg::TimerGroup;
 use esp_println as _;
 use modules::audio::audio_task;
 use modules::connectivity::wifi::wifi_init;
-use modules::indicator::indicator_task;
+use modules::crash::{SAFE_MODE_EFFECT, crash_guard_task, crash_init, record_panic};
+use modules::events::events_task;
+use modules::indicator::{Priority, indicator_task, set_effect};
 use modules::interaction::interaction_task;
+use modules::leds::driver::CLOCK_RATE;
+use modules::leds::leds_task;
 use modules::mode::{SystemMode, initialize_mode};
+use modules::power::power_task;
+use modules::quiet::quiet_task;
 use modules::servo::controller::ServoController;
 use modules::servo::servo_task;
+use modules::settings::{settings_init, settings_task};
+use modules::status::status_task;
+use modules::touch::touch_task;
+use modules::watchdog::watchdog_task;
 use ringbuf::{StaticRb, traits::*};
 use static_cell::StaticCell;
 
+use crate::modules::connectivity::clock::clock_task;
 use crate::modules::connectivity::mqtt::mqtt_init;
 use crate::modules::connectivity::streamer::{
     AudioChunk, STREAM_SIZE, StreamRingBuffer, streamer_init,
 };
+use crate::modules::connectivity::telemetry::telemetry_task;
 use crate::modules::motion::motion_task;
-use crate::modules::servo::animation::ANIMATION_QUEUE;
+use crate::modules::servo::animation::{ANIMATION_QUEUE, AnimationRequest};
 use crate::modules::servo::animations::AnimationType;
 
 esp_bootloader_esp_idf::esp_app_desc!();
@@ -36,7 +51,9 @@ esp_bootloader_esp_idf::esp_app_desc!();
 #[panic_handler]
 fn panic(panic_info: &core::panic::PanicInfo) -> ! {
     error!("{}", panic_info);
-    loop {}
+    // Reset instead of hanging, the report is published after the reboot
+    record_panic(panic_info);
+    software_reset();
 }
 
 extern crate alloc;
@@ -64,12 +81,45 @@ async fn main(spawner: Spawner) {
 
     info!("Embassy initialized!");
 
+    let safe_mode = crash_init();
+
+    // Settings
+    if let Some(storage) = settings_init(peripherals.FLASH) {
+        spawner.spawn(settings_task(storage).unwrap());
+    }
+
     // Indicator
-    spawner.spawn(indicator_task(peripherals.RMT, peripherals.GPIO21.into()).unwrap());
+    let rmt = Rmt::new(peripherals.RMT, CLOCK_RATE).unwrap().into_async();
+    spawner.spawn(indicator_task(rmt.channel3, peripherals.GPIO21.into()).unwrap());
 
     // Mode
     let system_mode = initialize_mode(spawner, peripherals.GPIO8, peripherals.GPIO9).await;
 
+    // Only connect to report the crashes, until the next reset
+    if safe_mode {
+        set_effect(Priority::Status, SAFE_MODE_EFFECT);
+        let wifi_stack = wifi_init(spawner, peripherals.WIFI, true);
+        spawner.spawn(mqtt_init(wifi_stack).unwrap());
+        return;
+    }
+
+    spawner.spawn(crash_guard_task().unwrap());
+
+    // Watchdog
+    let timg1 = TimerGroup::new(peripherals.TIMG1);
+    spawner.spawn(watchdog_task(timg1.wdt).unwrap());
+
+    spawner.spawn(status_task().unwrap());
+
+    // Reactions to everything the owl notices
+    spawner.spawn(events_task().unwrap());
+
+    // Goes quiet during the quiet hours, once the clock is set over the network
+    spawner.spawn(quiet_task().unwrap());
+
+    // Eyes and chest
+    spawner.spawn(leds_task(rmt.channel0, peripherals.GPIO4.into()).unwrap());
+
     // Servos
     let servo_controller = ServoController::new(
         peripherals.MCPWM0,
@@ -86,6 +136,15 @@ async fn main(spawner: Spawner) {
     let task = interaction_task(peripherals.GPIO6.into());
     spawner.spawn(task.unwrap());
 
+    // Head and wings
+    let touch_pins = [
+        peripherals.GPIO1.into(),
+        peripherals.GPIO2.into(),
+        peripherals.GPIO5.into(),
+        peripherals.GPIO7.into(),
+    ];
+    spawner.spawn(touch_task(peripherals.SENS, touch_pins).unwrap());
+
     let stream_ring_buffer =
         STREAM_RING_BUFFER.init(StaticRb::<AudioChunk, STREAM_SIZE>::default());
     let (stream_producer, stream_consumer) = stream_ring_buffer.split_ref();
@@ -97,33 +156,39 @@ async fn main(spawner: Spawner) {
         peripherals.GPIO36.into(),
         peripherals.GPIO37.into(),
         peripherals.GPIO35.into(),
+        peripherals.GPIO17.into(),
         stream_consumer,
     );
 
     spawner.spawn(task.unwrap());
 
-    match system_mode {
-        SystemMode::Mailbox => {
-            // Wifi
-            let wifi_stack = wifi_init(spawner, peripherals.WIFI).await;
-
-            // MQTT
-            spawner.spawn(mqtt_init(wifi_stack.clone()).unwrap());
-            spawner.spawn(streamer_init(wifi_stack, stream_producer).unwrap());
-        }
-        SystemMode::Play => {
-            ANIMATION_QUEUE.send(AnimationType::Yap).await;
-            // Accelerometer / Gyroscope
-            let task = motion_task(
-                peripherals.I2C0,
-                peripherals.GPIO41.into(),
-                peripherals.GPIO40.into(),
-                peripherals.GPIO39.into(),
-            );
-
-            spawner.spawn(task.unwrap());
-        }
-        SystemMode::Off => (),
+    // Wifi, it only connects in the modes that use the network
+    let wifi_stack = wifi_init(spawner, peripherals.WIFI, false);
+
+    // MQTT
+    spawner.spawn(mqtt_init(wifi_stack.clone()).unwrap());
+    spawner.spawn(streamer_init(wifi_stack, stream_producer).unwrap());
+    spawner.spawn(telemetry_task(wifi_stack).unwrap());
+    spawner.spawn(clock_task(wifi_stack).unwrap());
+
+    // Accelerometer / Gyroscope, only calibrated and tuned remotely in Mailbox mode
+    let task = motion_task(
+        peripherals.I2C0,
+        peripherals.GPIO41.into(),
+        peripherals.GPIO40.into(),
+        peripherals.GPIO39.into(),
+        peripherals.GPIO38.into(),
+    );
+
+    spawner.spawn(task.unwrap());
+
+    // Sleep when nobody plays with the owl, modes that use the network only doze for MQTT
+    spawner.spawn(power_task(Rtc::new(peripherals.LPWR)).unwrap());
+
+    if system_mode == SystemMode::Play {
+        ANIMATION_QUEUE
+            .send(AnimationRequest::new(AnimationType::Yap))
+            .await;
     }
 
     let stats: HeapStats = esp_alloc::HEAP.stats();
@@ -0,0 +1,8 @@
+/// Mono samples waiting between the sources and the DMA, 256 ms at 16 kHz.
+pub const OUTPUT_RING_SIZE: usize = 4_096;
+/// Samples the sources wait for before writing more, so they don't wake up for every frame.
+/// Also the most silence the output pads at once when they are late.
+pub const OUTPUT_MIN_WRITE: usize = 256;
+/// Below this many queued bytes the DMA is about to run dry, and gets silence if nothing is
+/// waiting. About 64 ms of stereo.
+pub const OUTPUT_LOW_WATER: usize = 4_092;
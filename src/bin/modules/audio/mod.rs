@@ -1,32 +1,105 @@
+use core::cell::Cell;
+
+use config::{OUTPUT_MIN_WRITE, OUTPUT_RING_SIZE};
 use defmt::info;
+use embassy_futures::join::join;
 use embassy_futures::select::{Either, select};
 use embassy_futures::yield_now;
+use embassy_sync::blocking_mutex::Mutex;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::signal::Signal;
-use embassy_time::{Duration, Instant};
-use esp_hal::i2s::master::Config;
+use embassy_time::{Duration, Instant, Timer};
 use esp_hal::peripherals::DMA_CH0;
-use esp_hal::{
-    Async, dma_buffers,
-    gpio::{AnyPin, OutputPin},
-    i2s::master::{DataFormat, I2s, I2sTx},
-    peripherals::I2S0,
-    time::Rate,
+use esp_hal::{gpio::AnyPin, peripherals::I2S0};
+use format::{FrameCarry, PcmFormat};
+use output::{
+    AudioOutput, OUTPUT_DATA, OUTPUT_SPACE, OutputProducer, OutputRing, set_source_active,
 };
-use ringbuf::traits::Consumer;
+use resampler::Resampler;
+use ringbuf::traits::{Consumer, Observer, Producer, SplitRef};
+use static_cell::StaticCell;
 use tracks::Tracks;
+use volume::{Normalizer, VolumeChannel, apply_gain, combine, percent_to_gain};
 
 use crate::modules::connectivity::streamer::StreamConsumer;
+use crate::modules::power::PAUSED;
+use crate::modules::quiet::{config::QUIET_VOLUME, is_quiet};
+use crate::modules::settings::{get_settings, update_settings};
+use crate::modules::watchdog::{SupervisedTask, check_in, idle};
 
+pub mod config;
+pub mod output;
 pub mod tracks;
 
+pub use owlimatronic_logic::audio::{config::SAMPLE_RATE, format, resampler, volume};
+
 pub static AUDIO_QUEUE: Signal<CriticalSectionRawMutex, Tracks> = Signal::new();
-pub static AUDIO_STREAM: Signal<CriticalSectionRawMutex, bool> = Signal::new();
+/// A stream started, in the format it is sent in.
+pub static AUDIO_STREAM: Signal<CriticalSectionRawMutex, PcmFormat> = Signal::new();
+
+/// What the audio task started playing last, and when.
+pub static NOW_PLAYING: Mutex<CriticalSectionRawMutex, Cell<Option<Playback>>> =
+    Mutex::new(Cell::new(None));
 
-static BUFFER_SIZE: usize = 4 * 4092;
+static MUTED: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// A stream ends when nothing came for this long.
+const STREAM_TIMEOUT: Duration = Duration::from_secs(1);
+/// Time between two checks for stream chunks.
+const STREAM_POLL: Duration = Duration::from_millis(10);
+
+static OUTPUT_RING: StaticCell<OutputRing> = StaticCell::new();
 
 const TAG: &str = "[AUDIO]";
 
+#[derive(Clone, Copy)]
+pub enum AudioSource {
+    Track(Tracks),
+    Stream,
+}
+
+impl AudioSource {
+    fn get_volume_channel(&self) -> VolumeChannel {
+        match self {
+            AudioSource::Track(_) => VolumeChannel::Track,
+            AudioSource::Stream => VolumeChannel::Stream,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Playback {
+    pub source: AudioSource,
+    pub started: Instant,
+}
+
+/// Silence the tracks of animations, returns whether they are muted now.
+///
+/// Live streams still play, someone sends them on purpose.
+pub fn toggle_mute() -> bool {
+    MUTED.lock(|muted| {
+        muted.set(!muted.get());
+        muted.get()
+    })
+}
+
+/// Set a volume in percent, it is saved in the settings.
+pub fn set_volume(channel: VolumeChannel, percent: u8) {
+    update_settings(|settings| settings.volume.set(channel, percent));
+}
+
+pub fn set_normalize_streams(normalize: bool) {
+    update_settings(|settings| settings.volume.normalize_streams = normalize);
+}
+
+fn set_now_playing(source: AudioSource) {
+    let playback = Playback {
+        source,
+        started: Instant::now(),
+    };
+    NOW_PLAYING.lock(|now_playing| now_playing.set(Some(playback)));
+}
+
 #[embassy_executor::task]
 pub async fn audio_task(
     i2s_peripheral: I2S0<'static>,
@@ -34,69 +107,71 @@ pub async fn audio_task(
     clock_pin: AnyPin<'static>,
     data_pin: AnyPin<'static>,
     ws_pin: AnyPin<'static>,
+    amplifier_pin: AnyPin<'static>,
     stream_consumer: StreamConsumer,
 ) {
-    let mut audio_controller = AudioService::new(
+    let (producer, consumer) = OUTPUT_RING.init(OutputRing::default()).split_ref();
+
+    let mut output = AudioOutput::new(
         i2s_peripheral,
         dma_channel,
         clock_pin,
         data_pin,
         ws_pin,
-        stream_consumer,
-    )
-    .await;
+        amplifier_pin,
+        consumer,
+    );
+    let mut audio_controller = AudioService::new(stream_consumer, producer);
     info!("{} task started", TAG);
-    loop {
-        audio_controller.run_loop().await;
-    }
+
+    // The sources fill the ring while the output plays it
+    let sources = async {
+        let mut paused = PAUSED.receiver().unwrap();
+        loop {
+            let pause = paused.get_and(|paused| *paused);
+            if let Either::Second(_) = select(audio_controller.run_loop(), pause).await {
+                info!("{} Paused", TAG);
+                set_source_active(false);
+                idle(SupervisedTask::Audio, paused.get_and(|paused| !*paused)).await;
+                AUDIO_QUEUE.reset();
+                AUDIO_STREAM.reset();
+            }
+        }
+    };
+    join(output.run(), sources).await;
 }
 
 pub struct AudioService {
-    tx: I2sTx<'static, Async>,
-    tx_buffer: &'static mut [u8; BUFFER_SIZE],
+    producer: OutputProducer,
     stream_consumer: StreamConsumer,
+    normalizer: Normalizer,
+    resampler: Resampler,
+    carry: FrameCarry,
 }
 
 impl AudioService {
-    pub async fn new(
-        i2s_peripheral: I2S0<'static>,
-        dma_channel: DMA_CH0<'static>,
-        clock_pin: impl OutputPin + 'static,
-        data_pin: impl OutputPin + 'static,
-        ws_pin: impl OutputPin + 'static,
-        stream_consumer: StreamConsumer,
-    ) -> Self {
-        let (_, _rx_descriptors, tx_buffer, tx_descriptors) = dma_buffers!(0, BUFFER_SIZE);
-
-        let config = Config::default()
-            .with_sample_rate(Rate::from_hz(16000u32))
-            .with_data_format(DataFormat::Data16Channel16);
-
-        let i2s = I2s::new(i2s_peripheral, dma_channel, config)
-            .unwrap()
-            .into_async();
-
-        let tx = i2s
-            .i2s_tx
-            .with_bclk(clock_pin)
-            .with_dout(data_pin)
-            .with_ws(ws_pin)
-            .build(tx_descriptors);
-
+    pub fn new(stream_consumer: StreamConsumer, producer: OutputProducer) -> Self {
         AudioService {
-            tx,
-            tx_buffer,
+            producer,
             stream_consumer,
+            normalizer: Normalizer::new(),
+            resampler: Resampler::new(SAMPLE_RATE, SAMPLE_RATE),
+            carry: FrameCarry::new(),
         }
     }
 
     async fn run_loop(&mut self) {
-        match select(AUDIO_QUEUE.wait(), AUDIO_STREAM.wait()).await {
+        let next = select(AUDIO_QUEUE.wait(), AUDIO_STREAM.wait());
+        match idle(SupervisedTask::Audio, next).await {
             Either::First(track) => {
-                self.play_track(track).await;
+                if MUTED.lock(|muted| muted.get()) {
+                    info!("{} muted, skipping '{}'", TAG, track.get_name());
+                } else {
+                    self.play_track(track).await;
+                }
             }
-            Either::Second(_) => {
-                self.stream_live().await;
+            Either::Second(format) => {
+                self.stream_live(format).await;
             }
         }
 
@@ -113,59 +188,112 @@ impl AudioService {
             pcm.len()
         );
 
-        self.play_mono_pcm(&pcm).await;
+        let source = AudioSource::Track(track);
+        set_now_playing(source);
+        set_source_active(true);
+        self.play_pcm(&pcm, track.get_format(), source).await;
+        set_source_active(false);
         info!("{} done playing local file", TAG);
     }
 
-    /// Stream live mono PCM
-    async fn stream_live(&mut self) {
-        info!("{} starting live PCM stream", TAG);
+    /// Stream live PCM
+    async fn stream_live(&mut self, format: PcmFormat) {
+        info!(
+            "{} starting live PCM stream ({}Hz, {} channels)",
+            TAG, format.sample_rate, format.channels
+        );
+        set_now_playing(AudioSource::Stream);
+        set_source_active(true);
+        self.normalizer.reset();
+        self.resampler = Resampler::new(format.sample_rate, SAMPLE_RATE);
+        self.carry.reset();
 
-        let start_time = Instant::now();
+        let mut last_chunk = Instant::now();
 
         loop {
+            check_in(SupervisedTask::Audio);
             while let Some(chunk) = self.stream_consumer.try_pop() {
-                self.write_mono_chunk(&chunk.data[..chunk.len]).await;
+                self.write_chunk(&chunk.data[..chunk.len], format, AudioSource::Stream)
+                    .await;
+                last_chunk = Instant::now();
             }
 
-            if Instant::now() - start_time > Duration::from_secs(1) {
+            // A late chunk is an underrun of the output, only a long pause ends the stream
+            if Instant::now() - last_chunk > STREAM_TIMEOUT {
                 break;
             }
 
-            yield_now().await;
+            Timer::after(STREAM_POLL).await;
         }
 
+        set_source_active(false);
         info!("{} live stream ended", TAG);
     }
 
-    /// Play a complete mono PCM buffer
-    async fn play_mono_pcm(&mut self, pcm: &[u8]) {
-        let chunk_size = self.tx_buffer.len() / 2;
-        let mut pos = 0;
+    /// Play a complete PCM buffer
+    async fn play_pcm(&mut self, pcm: &[u8], format: PcmFormat, source: AudioSource) {
+        self.resampler = Resampler::new(format.sample_rate, SAMPLE_RATE);
+        self.carry.reset();
+
+        // Whole frames, a quarter of the ring
+        let chunk_size = OUTPUT_RING_SIZE / 4 * format.get_frame_size();
 
-        while pos < pcm.len() {
-            let end = (pos + chunk_size).min(pcm.len());
-            let chunk = &pcm[pos..end];
-            self.write_mono_chunk(chunk).await;
-            pos = end;
+        for chunk in pcm.chunks(chunk_size) {
+            self.write_chunk(chunk, format, source).await;
             yield_now().await;
         }
     }
 
-    /// Convert a mono chunk to stereo and send it to I2S via DMA
-    async fn write_mono_chunk(&mut self, chunk: &[u8]) {
-        // Each sample = 2 bytes (16-bit)
-        let stereo_len = chunk.len() * 2;
-        let stereo = &mut self.tx_buffer[..stereo_len];
+    /// Gain of the next chunk, from the volumes, quiet mode and the loudness of streams.
+    fn get_gain(&mut self, chunk: &[u8], format: PcmFormat, source: AudioSource) -> u32 {
+        let volume = get_settings().volume;
+        let mut gain = combine(
+            percent_to_gain(volume.master),
+            percent_to_gain(volume.get(source.get_volume_channel())),
+        );
+
+        if matches!(source, AudioSource::Stream) && volume.normalize_streams {
+            gain = combine(gain, self.normalizer.update(format.samples(chunk)));
+        }
 
-        // Duplicate mono samples into left/right
-        for (i, sample) in chunk.chunks_exact(2).enumerate() {
-            stereo[i * 4..i * 4 + 2].copy_from_slice(sample); // Left
-            stereo[i * 4 + 2..i * 4 + 4].copy_from_slice(sample); // Right
+        if is_quiet() {
+            gain = combine(gain, percent_to_gain(QUIET_VOLUME));
         }
 
-        if let Err(e) = self.tx.write_dma_async(stereo).await {
-            info!("{} DMA error: {:?}", TAG, e);
+        gain
+    }
+
+    /// Convert a chunk to the output format and queue it for the output
+    async fn write_chunk(&mut self, chunk: &[u8], format: PcmFormat, source: AudioSource) {
+        check_in(SupervisedTask::Audio);
+
+        // Streams can split a frame between two chunks
+        let (mut carried, mut input) = self.carry.split(chunk, format);
+
+        // Every source plays through here, so the volumes and quiet mode cover them all
+        let gain = self.get_gain(input, format, source);
+
+        while carried.is_some() || !input.is_empty() {
+            // The output makes room as fast as it plays
+            while self.producer.vacant_len() < OUTPUT_MIN_WRITE {
+                OUTPUT_SPACE.wait().await;
+            }
+
+            let frames = self.resampler.get_max_input(self.producer.vacant_len())
+                - carried.is_some() as usize;
+            let length = (frames * format.get_frame_size()).min(input.len());
+            let (now, rest) = input.split_at(length);
+
+            // Downmix to mono and resample, through the gain and the limiter
+            let producer = &mut self.producer;
+            for sample in carried.take().into_iter().chain(format.samples(now)) {
+                self.resampler.push(sample, |output| {
+                    producer.try_push(apply_gain(output, gain)).ok();
+                });
+            }
+
+            input = rest;
+            OUTPUT_DATA.signal(());
         }
     }
 }
@@ -0,0 +1,272 @@
+use alloc::string::String;
+use core::{cell::Cell, fmt::Write};
+
+use defmt::{info, warn};
+use embassy_futures::select::select;
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    signal::Signal,
+};
+use embassy_time::{Duration, Timer};
+use esp_hal::{
+    Async, dma_circular_buffers,
+    gpio::{Level, Output, OutputConfig, OutputPin},
+    i2s::master::{Config, DataFormat, I2s, I2sTx},
+    peripherals::{DMA_CH0, I2S0},
+    time::Rate,
+};
+use ringbuf::{
+    StaticRb,
+    traits::{Consumer, Observer},
+    wrap::caching::Caching,
+};
+
+use crate::modules::{
+    connectivity::mqtt::{AUDIO_METRICS_TOPIC, publish},
+    mode::get_mode,
+};
+
+use super::{
+    SAMPLE_RATE, TAG,
+    config::{OUTPUT_LOW_WATER, OUTPUT_MIN_WRITE, OUTPUT_RING_SIZE},
+};
+
+/// Stereo 16-bit, 256 ms at 16 kHz.
+const BUFFER_SIZE: usize = 4 * 4092;
+const FRAME_SIZE: usize = 4;
+
+/// Mono samples at [`SAMPLE_RATE`], from the sources to the DMA.
+pub type OutputRing = StaticRb<i16, OUTPUT_RING_SIZE>;
+pub type OutputProducer = Caching<&'static OutputRing, true, false>;
+pub type OutputConsumer = Caching<&'static OutputRing, false, true>;
+
+/// Raised when samples were written or a source started or stopped.
+pub static OUTPUT_DATA: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+/// Raised when the DMA took samples, so there is room in the ring again.
+pub static OUTPUT_SPACE: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Whether a source is playing, running out of samples then is an underrun.
+static SOURCE_ACTIVE: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+
+pub fn set_source_active(active: bool) {
+    SOURCE_ACTIVE.lock(|cell| cell.set(active));
+    OUTPUT_DATA.signal(());
+}
+
+fn is_source_active() -> bool {
+    SOURCE_ACTIVE.lock(|cell| cell.get())
+}
+
+/// Measured from the start of the DMA transfer until it stops again.
+#[derive(Clone, Copy, Default)]
+pub struct OutputMetrics {
+    /// Times the DMA ran dry while a source was playing, filled with silence.
+    pub underruns: u32,
+    /// Time a sample written by a source takes to reach the speaker.
+    pub latency: Duration,
+    pub max_latency: Duration,
+}
+
+impl OutputMetrics {
+    /// {"underruns":0,"latency_ms":120,"max_latency_ms":250}
+    fn to_json(&self) -> String {
+        let mut json = String::new();
+        write!(
+            json,
+            "{{\"underruns\":{},\"latency_ms\":{},\"max_latency_ms\":{}}}",
+            self.underruns,
+            self.latency.as_millis(),
+            self.max_latency.as_millis()
+        )
+        .ok();
+        json
+    }
+}
+
+fn frames_to_duration(frames: usize) -> Duration {
+    Duration::from_micros(frames as u64 * 1_000_000 / SAMPLE_RATE as u64)
+}
+
+/// Feeds the speaker from the ring with a circular DMA transfer, so there is no gap between
+/// two writes. The transfer only runs while there is something to play.
+pub struct AudioOutput {
+    tx: I2sTx<'static, Async>,
+    /// Shutdown pin of the amplifier, it is only on while the transfer runs.
+    amplifier: Output<'static>,
+    tx_buffer: &'static mut [u8; BUFFER_SIZE],
+    consumer: OutputConsumer,
+    metrics: OutputMetrics,
+}
+
+impl AudioOutput {
+    pub fn new(
+        i2s_peripheral: I2S0<'static>,
+        dma_channel: DMA_CH0<'static>,
+        clock_pin: impl OutputPin + 'static,
+        data_pin: impl OutputPin + 'static,
+        ws_pin: impl OutputPin + 'static,
+        amplifier_pin: impl OutputPin + 'static,
+        consumer: OutputConsumer,
+    ) -> Self {
+        let (_, _rx_descriptors, tx_buffer, tx_descriptors) = dma_circular_buffers!(0, BUFFER_SIZE);
+
+        let config = Config::default()
+            .with_sample_rate(Rate::from_hz(SAMPLE_RATE))
+            .with_data_format(DataFormat::Data16Channel16);
+
+        let i2s = I2s::new(i2s_peripheral, dma_channel, config)
+            .unwrap()
+            .into_async();
+
+        let tx = i2s
+            .i2s_tx
+            .with_bclk(clock_pin)
+            .with_dout(data_pin)
+            .with_ws(ws_pin)
+            .build(tx_descriptors);
+
+        AudioOutput {
+            tx,
+            amplifier: Output::new(amplifier_pin, Level::Low, OutputConfig::default()),
+            tx_buffer,
+            consumer,
+            metrics: OutputMetrics::default(),
+        }
+    }
+
+    pub async fn run(&mut self) -> ! {
+        loop {
+            // The DMA stays stopped while there is nothing to play
+            OUTPUT_DATA.wait().await;
+            if self.consumer.is_empty() {
+                continue;
+            }
+
+            self.metrics = OutputMetrics::default();
+            self.amplifier.set_high();
+            self.play().await;
+            self.amplifier.set_low();
+
+            info!(
+                "{} Output stopped, {} underruns, latency {}ms (max {}ms)",
+                TAG,
+                self.metrics.underruns,
+                self.metrics.latency.as_millis(),
+                self.metrics.max_latency.as_millis()
+            );
+            if get_mode().is_networked() {
+                publish(AUDIO_METRICS_TOPIC, self.metrics.to_json(), false);
+            }
+        }
+    }
+
+    /// Run the transfer until the sources stopped and everything they wrote was played.
+    async fn play(&mut self) {
+        // The transfer starts with the whole buffer queued
+        let prefilled = fill_frames(&mut self.consumer, &mut self.tx_buffer[..]);
+        self.tx_buffer[prefilled * FRAME_SIZE..].fill(0);
+        OUTPUT_SPACE.signal(());
+
+        let mut transfer = match self.tx.write_dma_circular_async(&mut *self.tx_buffer) {
+            Ok(transfer) => transfer,
+            Err(e) => {
+                warn!("{} DMA error: {:?}", TAG, e);
+                return;
+            }
+        };
+
+        // Bytes of silence queued since the last sample, the transfer stops once a whole
+        // buffer of it was queued after the sources stopped
+        let mut silence = 0;
+        let mut underrun = false;
+
+        loop {
+            let free = match transfer.available().await {
+                Ok(free) => free,
+                Err(e) => {
+                    // Fell behind, the DMA played old samples again
+                    warn!("{} DMA error: {:?}", TAG, e);
+                    self.metrics.underruns += 1;
+                    return;
+                }
+            };
+
+            let queued = BUFFER_SIZE - free;
+            let waiting = self.consumer.occupied_len();
+            let active = is_source_active();
+
+            let latency = frames_to_duration(waiting + queued / FRAME_SIZE);
+            self.metrics.latency = latency;
+            self.metrics.max_latency = self.metrics.max_latency.max(latency);
+
+            // Silence only goes in when there is nothing else, never between two samples
+            let pad = waiting == 0;
+            if pad {
+                if !active && silence >= BUFFER_SIZE {
+                    return;
+                }
+
+                // Plenty left to play, give the sources time to catch up before padding
+                if active && queued > OUTPUT_LOW_WATER {
+                    let spare = frames_to_duration((queued - OUTPUT_LOW_WATER) / FRAME_SIZE);
+                    select(OUTPUT_DATA.wait(), Timer::after(spare)).await;
+                    continue;
+                }
+
+                // Count a run of silence once
+                if active && !underrun {
+                    self.metrics.underruns += 1;
+                }
+                underrun = active;
+            } else {
+                underrun = false;
+            }
+
+            let consumer = &mut self.consumer;
+            let mut padding = 0;
+            let result = transfer
+                .push_with(|buffer| {
+                    let length = buffer.len() / FRAME_SIZE * FRAME_SIZE;
+                    let written = fill_frames(consumer, &mut buffer[..length]) * FRAME_SIZE;
+                    if !pad {
+                        return written;
+                    }
+
+                    // Only a little, so the sources' next samples aren't queued behind it
+                    let padded = length.min(written + OUTPUT_MIN_WRITE * FRAME_SIZE);
+                    buffer[written..padded].fill(0);
+                    padding = padded - written;
+                    padded
+                })
+                .await;
+
+            match result {
+                Ok(written) if written == padding => silence += padding,
+                Ok(_) => silence = padding,
+                Err(e) => {
+                    warn!("{} DMA error: {:?}", TAG, e);
+                    self.metrics.underruns += 1;
+                    return;
+                }
+            }
+
+            OUTPUT_SPACE.signal(());
+        }
+    }
+}
+
+/// Move samples from the ring into `buffer` as stereo frames, returns the number of frames.
+fn fill_frames(consumer: &mut OutputConsumer, buffer: &mut [u8]) -> usize {
+    let mut frames = 0;
+    for frame in buffer.chunks_exact_mut(FRAME_SIZE) {
+        let Some(sample) = consumer.try_pop() else {
+            break;
+        };
+
+        let sample = sample.to_le_bytes();
+        frame[0..2].copy_from_slice(&sample); // Left
+        frame[2..4].copy_from_slice(&sample); // Right
+        frames += 1;
+    }
+    frames
+}
@@ -1,4 +1,6 @@
-#[derive(Clone, Copy, Debug)]
+use super::format::PcmFormat;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Tracks {
     BuboRatched1,
     BuboRatched2,
@@ -13,7 +15,8 @@ pub enum Tracks {
     BuboYap8,
 }
 
-// ffmpeg -i sound.mp3 -ac 1 -ar 16000 -f s16le -c:a pcm_s16le sound.pcm
+// ffmpeg -i sound.mp3 -f s16le -c:a pcm_s16le sound.pcm
+// Any rate from 8 to 48 kHz and mono or stereo plays, as long as get_format says so
 
 // impl format
 impl Tracks {
@@ -32,6 +35,23 @@ impl Tracks {
             Tracks::BuboYap8 => "Bubo Yap 8",
         }
     }
+
+    pub fn get_from_binary(payload: &[u8]) -> Option<Tracks> {
+        match payload {
+            b"bubo_ratched_1" => Some(Tracks::BuboRatched1),
+            b"bubo_ratched_2" => Some(Tracks::BuboRatched2),
+            b"bubo_ratched_3" => Some(Tracks::BuboRatched3),
+            b"bubo_yap_1" => Some(Tracks::BuboYap1),
+            b"bubo_yap_2" => Some(Tracks::BuboYap2),
+            b"bubo_yap_3" => Some(Tracks::BuboYap3),
+            b"bubo_yap_4" => Some(Tracks::BuboYap4),
+            b"bubo_yap_5" => Some(Tracks::BuboYap5),
+            b"bubo_yap_6" => Some(Tracks::BuboYap6),
+            b"bubo_yap_7" => Some(Tracks::BuboYap7),
+            b"bubo_yap_8" => Some(Tracks::BuboYap8),
+            _ => None,
+        }
+    }
 }
 
 impl Tracks {
@@ -50,4 +70,9 @@ impl Tracks {
             Tracks::BuboYap8 => include_bytes!("bubo_yap_8.pcm"),
         }
     }
+
+    pub fn get_format(&self) -> PcmFormat {
+        // All converted with -ac 1 -ar 16000 so far
+        PcmFormat::OUTPUT
+    }
 }
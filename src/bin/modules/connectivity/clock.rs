@@ -0,0 +1,181 @@
+use core::cell::Cell;
+
+use defmt::{info, warn};
+use embassy_net::{
+    IpEndpoint, Stack,
+    dns::DnsQueryType,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    signal::Signal,
+};
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+
+const TAG: &str = "[CLOCK]";
+
+static NTP_SERVER: &str = match option_env!("NTP_SERVER") {
+    Some(server) => server,
+    None => "pool.ntp.org",
+};
+/// Seconds ahead of UTC, so the schedules follow local time. Set in minutes, like `-300`.
+const UTC_OFFSET: i64 = match option_env!("UTC_OFFSET") {
+    Some(offset) => parse_offset(offset) * 60,
+    None => 0,
+};
+
+const NTP_PORT: u16 = 123;
+const NTP_PACKET_SIZE: usize = 48;
+/// Seconds from 1900, where NTP starts counting, to 1970.
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+/// The clock drifts, so it is set again now and then while the network is up.
+const SYNC_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// Unix time of [`Instant`] zero in milliseconds, `None` until the clock was set.
+static BOOT_TIME: Mutex<CriticalSectionRawMutex, Cell<Option<u64>>> = Mutex::new(Cell::new(None));
+
+/// Raised every time the clock is set.
+static CLOCK_SET: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Parse a number of minutes at compile time, a bad offset fails the build.
+const fn parse_offset(offset: &str) -> i64 {
+    let digits = offset.as_bytes();
+    let (negative, start) = match digits {
+        [b'-', ..] => (true, 1),
+        [b'+', ..] => (false, 1),
+        _ => (false, 0),
+    };
+    assert!(start < digits.len(), "UTC_OFFSET is empty");
+
+    let mut minutes = 0;
+    let mut index = start;
+    while index < digits.len() {
+        assert!(
+            digits[index].is_ascii_digit(),
+            "UTC_OFFSET must be a whole number of minutes"
+        );
+        minutes = minutes * 10 + (digits[index] - b'0') as i64;
+        assert!(minutes <= 14 * 60, "UTC_OFFSET is more than 14 hours");
+        index += 1;
+    }
+
+    if negative { -minutes } else { minutes }
+}
+
+pub fn is_clock_set() -> bool {
+    BOOT_TIME.lock(|boot_time| boot_time.get()).is_some()
+}
+
+/// Wait for the clock to be set again, returns right away if it was set since the last call.
+pub async fn wait_for_sync() {
+    CLOCK_SET.wait().await
+}
+
+/// Unix time in seconds, once the clock was set.
+pub fn get_unix_time() -> Option<u64> {
+    BOOT_TIME
+        .lock(|boot_time| boot_time.get())
+        .map(|boot_time| (boot_time + Instant::now().as_millis()) / 1000)
+}
+
+/// Minutes since local midnight, once the clock was set.
+pub fn get_local_minutes() -> Option<u16> {
+    let local = get_unix_time()? as i64 + UTC_OFFSET;
+    Some((local.rem_euclid(24 * 60 * 60) / 60) as u16)
+}
+
+/// Move the clock on by time [`Instant`] didn't count, [`Instant`] stops during light sleep.
+pub fn skip(duration: Duration) {
+    BOOT_TIME.lock(|boot_time| {
+        boot_time.set(
+            boot_time
+                .get()
+                .map(|boot_time| boot_time + duration.as_millis()),
+        )
+    });
+}
+
+/// Sets the clock over SNTP whenever the network is up.
+///
+/// Modes without the network connect until the clock was set once, light sleep is accounted
+/// for with [`skip`] so it stays set.
+#[embassy_executor::task]
+pub async fn clock_task(stack: Stack<'static>) {
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut rx_buffer = [0; NTP_PACKET_SIZE];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_buffer = [0; NTP_PACKET_SIZE];
+
+    loop {
+        stack.wait_config_up().await;
+
+        let mut socket = UdpSocket::new(
+            stack,
+            &mut rx_meta,
+            &mut rx_buffer,
+            &mut tx_meta,
+            &mut tx_buffer,
+        );
+        socket.bind(0).unwrap();
+
+        let delay = match sync(stack, &mut socket).await {
+            Ok(unix_time) => {
+                info!("{} Set to {}", TAG, unix_time);
+                SYNC_PERIOD
+            }
+            Err(error) => {
+                warn!("{} Sync failed: {}", TAG, error);
+                RETRY_DELAY
+            }
+        };
+
+        drop(socket);
+        Timer::after(delay).await;
+    }
+}
+
+/// Asks the server for the time and sets the clock, returns the unix time.
+async fn sync(stack: Stack<'static>, socket: &mut UdpSocket<'_>) -> Result<u64, &'static str> {
+    let address = stack
+        .dns_query(NTP_SERVER, DnsQueryType::A)
+        .await
+        .map_err(|_| "dns query failed")?
+        .first()
+        .copied()
+        .ok_or("no address")?;
+    let endpoint = IpEndpoint::new(address, NTP_PORT);
+
+    // Version 3, client mode, everything else can be left empty
+    let mut packet = [0u8; NTP_PACKET_SIZE];
+    packet[0] = 0x1B;
+
+    socket
+        .send_to(&packet, endpoint)
+        .await
+        .map_err(|_| "send failed")?;
+
+    let (length, _) = with_timeout(RESPONSE_TIMEOUT, socket.recv_from(&mut packet))
+        .await
+        .map_err(|_| "no response")?
+        .map_err(|_| "receive failed")?;
+
+    if length < NTP_PACKET_SIZE {
+        return Err("response too short");
+    }
+
+    // Seconds of the transmit timestamp
+    let ntp_time = u32::from_be_bytes(packet[40..44].try_into().unwrap()) as u64;
+    if ntp_time < NTP_UNIX_OFFSET {
+        return Err("invalid time");
+    }
+
+    let unix_time = ntp_time - NTP_UNIX_OFFSET;
+    let boot_time = unix_time * 1000 - Instant::now().as_millis();
+    BOOT_TIME.lock(|cell| cell.set(Some(boot_time)));
+    CLOCK_SET.signal(());
+
+    Ok(unix_time)
+}
@@ -1,3 +1,5 @@
+pub mod clock;
 pub mod mqtt;
 pub mod streamer;
+pub mod telemetry;
 pub mod wifi;
@@ -1,22 +1,42 @@
+use alloc::string::String;
 use core::num::NonZero;
 use defmt::{error, info, warn};
-use embassy_futures::select::{Either, select};
+use embassy_futures::select::{Either3, select3};
 use embassy_net::{IpEndpoint, Stack, tcp::TcpSocket};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
 use embassy_time::{Duration, Timer};
 use rust_mqtt::{
     buffer::*,
     client::{
         Client,
         event::Event,
-        options::{ConnectOptions, RetainHandling, SubscriptionOptions},
+        options::{ConnectOptions, PublicationOptions, RetainHandling, SubscriptionOptions},
     },
     config::{KeepAlive, SessionExpiryInterval},
-    types::{MqttBinary, MqttString, TopicFilter, VarByteInt},
+    types::{MqttBinary, MqttString, QoS, TopicFilter, TopicName, VarByteInt},
 };
 
 use crate::modules::{
+    audio::{
+        AUDIO_QUEUE, format::PcmFormat, set_normalize_streams, set_volume, tracks::Tracks,
+        volume::VolumeChannel,
+    },
     connectivity::streamer::STREAMER_TRIGGER,
-    servo::{animation::ANIMATION_QUEUE, animations::AnimationType},
+    crash::publish_crash_report,
+    events::{self, publish_event},
+    indicator::{Notification, Priority, clear_effect, effects::Effect, notify, set_effect},
+    leds::{LED_BRIGHTNESS, LedGroup, parse_color, set_group},
+    mode::{SystemMode, set_mode},
+    motion::{CALIBRATE_TRIGGER, debug::DEBUG_RATE},
+    quiet::{set_quiet, toggle_quiet},
+    servo::{
+        animations::AnimationType,
+        layers::{self, LayerId},
+        live::{LIVE_SERVO_QUEUE, parse_targets},
+        recorder::{RECORD_QUEUE, RecordCommand},
+    },
+    status::publish_status,
+    watchdog::{SupervisedTask, check_in, idle},
 };
 
 const TAG: &str = "[MQTT]";
@@ -28,7 +48,70 @@ static MQTT_PASSWORD: &str = env!("MQTT_PASSWORD");
 static MQTT_CLIENT_ID: &str = env!("MQTT_CLIENT_ID");
 static MQTT_PORT: &str = env!("MQTT_PORT");
 
+const SUBSCRIBE_TOPIC: &str = "owlimatronic/#";
+const EVENT_TOPIC: &str = "owlimatronic/event";
+/// Every event is mirrored here, see [`events::Event`].
+pub const EVENTS_TOPIC: &str = "owlimatronic/events";
+const SERVO_SET_TOPIC: &str = "owlimatronic/servo/set";
+const SERVO_RECORD_TOPIC: &str = "owlimatronic/servo/record";
+const AUDIO_PLAY_TOPIC: &str = "owlimatronic/audio/play";
+/// Underruns and latency of the audio output, after everything was played.
+pub const AUDIO_METRICS_TOPIC: &str = "owlimatronic/audio/metrics";
+const AUDIO_NORMALIZE_TOPIC: &str = "owlimatronic/audio/normalize";
+const AUDIO_VOLUME_TOPIC_PREFIX: &str = "owlimatronic/audio/volume/";
+const LAYER_TOPIC_PREFIX: &str = "owlimatronic/layer/";
+const MOTION_CALIBRATE_TOPIC: &str = "owlimatronic/motion/calibrate";
+const MOTION_DEBUG_TOPIC: &str = "owlimatronic/motion/debug";
+pub const STATUS_TOPIC: &str = "owlimatronic/status";
+pub const CRASH_TOPIC: &str = "owlimatronic/crash";
+const INDICATOR_TOPIC: &str = "owlimatronic/indicator";
+const LED_BRIGHTNESS_TOPIC: &str = "owlimatronic/leds/brightness";
+const LED_TOPIC_PREFIX: &str = "owlimatronic/leds/";
+const MODE_TOPIC: &str = "owlimatronic/mode";
+const QUIET_TOPIC: &str = "owlimatronic/quiet";
+
+static LAYER_FADE: Duration = Duration::from_secs(1);
+
 static RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// Messages waiting to be published, dropped while the queue is full.
+static MQTT_OUTBOX: Channel<CriticalSectionRawMutex, OutgoingMessage, 8> = Channel::new();
+
+pub struct OutgoingMessage {
+    topic: &'static str,
+    payload: String,
+    retain: bool,
+    /// Called once the message went out.
+    on_sent: Option<fn()>,
+}
+
+/// Publish once connected, retained messages are also seen by clients that connect later.
+pub fn publish(topic: &'static str, payload: String, retain: bool) {
+    queue(OutgoingMessage {
+        topic,
+        payload,
+        retain,
+        on_sent: None,
+    });
+}
+
+/// Publish once connected and call `on_sent` when it went out, it isn't called when the
+/// connection drops first.
+pub fn publish_and_confirm(topic: &'static str, payload: String, retain: bool, on_sent: fn()) {
+    queue(OutgoingMessage {
+        topic,
+        payload,
+        retain,
+        on_sent: Some(on_sent),
+    });
+}
+
+fn queue(message: OutgoingMessage) {
+    let topic = message.topic;
+    if MQTT_OUTBOX.try_send(message).is_err() {
+        warn!("{} Outbox full, dropping message for {}", TAG, topic);
+    }
+}
+
 type MqttClient<'c> = Client<'c, TcpSocket<'c>, BumpBuffer<'c>, 1, 1, 1, 1>;
 
 #[embassy_executor::task]
@@ -40,7 +123,10 @@ pub async fn mqtt_init(stack: Stack<'static>) {
     let endpoint = IpEndpoint::new(SERVER_IP.parse().unwrap(), MQTT_PORT.parse().unwrap());
 
     loop {
-        stack.wait_config_up().await;
+        idle(SupervisedTask::Mqtt, stack.wait_config_up()).await;
+        if let Some(config) = stack.config_v4() {
+            info!("{} Got IP: {}", TAG, config.address);
+        }
 
         if let Err(error) =
             mqtt_connect_and_run(stack, &mut tcp_rx, &mut tcp_tx, &mut mqtt_storage, endpoint).await
@@ -52,7 +138,7 @@ pub async fn mqtt_init(stack: Stack<'static>) {
                 error
             );
 
-            Timer::after(RECONNECT_DELAY).await;
+            idle(SupervisedTask::Mqtt, Timer::after(RECONNECT_DELAY)).await;
         }
     }
 }
@@ -95,16 +181,18 @@ async fn mqtt_connect_and_run<'c>(
         .await
         .map_err(|_| "mqtt connect failed")?;
 
+    // The owl publishes under the same prefix, it doesn't need its own messages back
     let mut sub_options = SubscriptionOptions::new()
         .retain_handling(RetainHandling::SendIfNotSubscribedBefore)
         .retain_as_published()
+        .no_local()
         .at_least_once();
 
     if client.server_config().subscription_identifiers_supported {
         sub_options.subscription_identifier = Some(VarByteInt::from(42u16));
     }
 
-    let topic = MqttString::from_str("owlimatronic/event").unwrap();
+    let topic = MqttString::from_str(SUBSCRIBE_TOPIC).unwrap();
 
     let filter = TopicFilter::new(topic.as_borrowed()).unwrap();
 
@@ -114,6 +202,9 @@ async fn mqtt_connect_and_run<'c>(
         .map_err(|_| "subscribe failed")?;
 
     info!("{} MQTT connected", TAG);
+    notify(Notification::MqttConnected);
+    publish_crash_report();
+    publish_status();
 
     mqtt_run(&mut client).await?;
 
@@ -122,26 +213,18 @@ async fn mqtt_connect_and_run<'c>(
 
 async fn mqtt_run(client: &mut MqttClient<'_>) -> Result<(), &'static str> {
     loop {
-        match select(client.poll(), Timer::after(Duration::from_secs(30))).await {
-            Either::First(result) => match result {
+        check_in(SupervisedTask::Mqtt);
+
+        match select3(
+            client.poll(),
+            MQTT_OUTBOX.receive(),
+            Timer::after(Duration::from_secs(30)),
+        )
+        .await
+        {
+            Either3::First(result) => match result {
                 Ok(Event::Publish(message)) => {
-                    let payload = message.message;
-
-                    info!("{} Received: {} {}", TAG, message.topic, payload);
-
-                    if payload.as_bytes() == b"stream" {
-                        STREAMER_TRIGGER.signal(());
-                    } else {
-                        let animation = match AnimationType::get_from_binary(&payload) {
-                            Some(anim) => anim,
-                            None => {
-                                warn!("{} Animation not found {}", TAG, payload);
-                                continue;
-                            }
-                        };
-
-                        ANIMATION_QUEUE.send(animation).await;
-                    }
+                    handle_message(message.topic.as_str(), message.message.as_bytes()).await;
                 }
 
                 Ok(Event::Pingresp) => (),
@@ -153,9 +236,166 @@ async fn mqtt_run(client: &mut MqttClient<'_>) -> Result<(), &'static str> {
                     return Err("poll failed");
                 }
             },
-            Either::Second(_) => {
+            Either3::Second(message) => {
+                let topic = MqttString::from_str(message.topic).unwrap();
+                let options = PublicationOptions {
+                    retain: message.retain,
+                    topic: TopicName::new(topic).unwrap(),
+                    qos: QoS::AtMostOnce,
+                };
+
+                client
+                    .publish(&options, message.payload.as_bytes().into())
+                    .await
+                    .map_err(|_| "publish failed")?;
+
+                if let Some(on_sent) = message.on_sent {
+                    on_sent();
+                }
+            }
+            Either3::Third(_) => {
                 client.ping().await.ok();
             }
         }
     }
 }
+
+async fn handle_message(topic: &str, payload: &[u8]) {
+    match topic {
+        // Live control is high rate, so don't log every message
+        SERVO_SET_TOPIC => match parse_targets(payload) {
+            Some(targets) => LIVE_SERVO_QUEUE.signal(targets),
+            None => warn!("{} Invalid servo targets {}", TAG, payload),
+        },
+        SERVO_RECORD_TOPIC => match RecordCommand::get_from_binary(payload) {
+            Some(command) => RECORD_QUEUE.signal(command),
+            None => warn!("{} Invalid record command {}", TAG, payload),
+        },
+        AUDIO_PLAY_TOPIC => match Tracks::get_from_binary(payload) {
+            Some(track) => AUDIO_QUEUE.signal(track),
+            None => warn!("{} Track not found {}", TAG, payload),
+        },
+        AUDIO_NORMALIZE_TOPIC => match payload {
+            b"on" => set_normalize_streams(true),
+            b"off" => set_normalize_streams(false),
+            _ => warn!("{} Invalid normalize state {}", TAG, payload),
+        },
+        MOTION_CALIBRATE_TOPIC => {
+            info!("{} Calibrating motion sensor", TAG);
+            CALIBRATE_TRIGGER.signal(());
+        }
+        MOTION_DEBUG_TOPIC => {
+            // Samples per second, 0 stops streaming
+            let rate = core::str::from_utf8(payload)
+                .ok()
+                .and_then(|p| p.parse::<u32>().ok());
+            match rate {
+                Some(rate) => {
+                    info!("{} Streaming IMU samples at {}Hz", TAG, rate);
+                    DEBUG_RATE.signal(rate);
+                }
+                None => warn!("{} Invalid debug rate {}", TAG, payload),
+            }
+        }
+        INDICATOR_TOPIC => match payload {
+            b"off" => clear_effect(Priority::Remote),
+            _ => match Effect::get_from_binary(payload) {
+                Some(effect) => set_effect(Priority::Remote, effect),
+                None => warn!("{} Effect not found {}", TAG, payload),
+            },
+        },
+        // Don't retain it, it would override the switch on every connect. Play mode turns the
+        // network off, so only the switch can pick it.
+        MODE_TOPIC => match SystemMode::get_from_binary(payload) {
+            Some(mode) => set_mode(mode),
+            None => warn!("{} Mode not found {}", TAG, payload),
+        },
+        QUIET_TOPIC => match payload {
+            b"on" => set_quiet(true),
+            b"off" => set_quiet(false),
+            b"toggle" => {
+                toggle_quiet();
+            }
+            _ => warn!("{} Invalid quiet state {}", TAG, payload),
+        },
+        LED_BRIGHTNESS_TOPIC => {
+            let brightness = core::str::from_utf8(payload)
+                .ok()
+                .and_then(|p| p.parse::<u8>().ok());
+            match brightness {
+                Some(brightness) => LED_BRIGHTNESS.signal(brightness),
+                None => warn!("{} Invalid brightness {}", TAG, payload),
+            }
+        }
+        t if t.starts_with(LED_TOPIC_PREFIX) => {
+            let name = &t.as_bytes()[LED_TOPIC_PREFIX.len()..];
+            let Some(group) = LedGroup::get_from_binary(name) else {
+                warn!("{} LED group not found {}", TAG, name);
+                return;
+            };
+
+            match parse_color(payload) {
+                Some(color) => set_group(group, color),
+                None => warn!("{} Invalid color {}", TAG, payload),
+            }
+        }
+        t if t.starts_with(AUDIO_VOLUME_TOPIC_PREFIX) => {
+            let name = &t.as_bytes()[AUDIO_VOLUME_TOPIC_PREFIX.len()..];
+            let Some(channel) = VolumeChannel::get_from_binary(name) else {
+                warn!("{} Volume not found {}", TAG, name);
+                return;
+            };
+
+            // Percent, above 100 boosts
+            let percent = core::str::from_utf8(payload)
+                .ok()
+                .and_then(|p| p.parse::<u8>().ok());
+            match percent {
+                Some(percent) => {
+                    info!("{} {} volume: {}%", TAG, channel.get_name(), percent);
+                    set_volume(channel, percent);
+                }
+                None => warn!("{} Invalid volume {}", TAG, payload),
+            }
+        }
+        t if t.starts_with(LAYER_TOPIC_PREFIX) => {
+            let name = &t.as_bytes()[LAYER_TOPIC_PREFIX.len()..];
+            let Some(layer) = LayerId::get_from_binary(name) else {
+                warn!("{} Layer not found {}", TAG, name);
+                return;
+            };
+
+            // Weight in percent
+            let weight = core::str::from_utf8(payload)
+                .ok()
+                .and_then(|p| p.parse::<u8>().ok());
+            match weight {
+                Some(percent) => layers::set_weight(layer, percent as f32 / 100.0, LAYER_FADE),
+                None => warn!("{} Invalid layer weight {}", TAG, payload),
+            }
+        }
+        EVENT_TOPIC => {
+            info!("{} Received: {} {}", TAG, topic, payload);
+
+            // "stream" for 16 kHz mono, or with the format like "stream:44100:2"
+            if payload == b"stream" {
+                STREAMER_TRIGGER.signal(PcmFormat::OUTPUT);
+                return;
+            }
+
+            if let Some(format) = payload.strip_prefix(b"stream:") {
+                match PcmFormat::get_from_binary(format) {
+                    Some(format) => STREAMER_TRIGGER.signal(format),
+                    None => warn!("{} Invalid stream format {}", TAG, format),
+                }
+                return;
+            }
+
+            match AnimationType::get_from_binary(payload) {
+                Some(animation) => publish_event(events::Event::MqttCommand(animation)),
+                None => warn!("{} Animation not found {}", TAG, payload),
+            }
+        }
+        _ => (),
+    }
+}
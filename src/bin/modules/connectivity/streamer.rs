@@ -7,11 +7,13 @@ use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal}
 use embassy_time::{Duration, Timer};
 use ringbuf::{SharedRb, storage::Owning, traits::Producer, wrap::caching::Caching};
 
-use crate::modules::audio::AUDIO_STREAM;
+use crate::modules::audio::{AUDIO_STREAM, format::PcmFormat};
+use crate::modules::events::{Event, publish_event};
 
 const TAG: &str = "[STREAMER]";
 
-pub static STREAMER_TRIGGER: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+/// Connect to the streamer, which sends PCM in the given format.
+pub static STREAMER_TRIGGER: Signal<CriticalSectionRawMutex, PcmFormat> = Signal::new();
 
 static SERVER_IP: &str = env!("SERVER_IP");
 static STREAMER_PORT: &str = env!("STREAMER_PORT");
@@ -46,7 +48,7 @@ pub async fn streamer_init(stack: Stack<'static>, mut stream: StreamProducer) {
 
     loop {
         // Wait for streamer trigger
-        STREAMER_TRIGGER.wait().await;
+        let format = STREAMER_TRIGGER.wait().await;
 
         // Ensure network stack is up
         if !stack.is_config_up() {
@@ -74,7 +76,8 @@ pub async fn streamer_init(stack: Stack<'static>, mut stream: StreamProducer) {
         reconnect_delay_secs = 1;
         info!("{} TCP connected!", TAG);
 
-        AUDIO_STREAM.signal(true);
+        AUDIO_STREAM.signal(format);
+        publish_event(Event::StreamStarted);
 
         let mut buffer = [0u8; 1024];
         let mut send_buffer = [0u8; AUDIO_CHUNK_SIZE];
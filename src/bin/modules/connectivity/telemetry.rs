@@ -0,0 +1,83 @@
+use alloc::string::String;
+use core::{net::Ipv4Addr, str::FromStr};
+
+use defmt::{info, warn};
+use embassy_net::{
+    Stack,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_time::{Duration, Instant};
+
+use crate::modules::motion::{
+    debug::DEBUG_SAMPLES,
+    trace::{TRACE_HEADER, write_trace_line},
+};
+
+const TAG: &str = "[TELEMETRY]";
+
+static SERVER_IP: &str = env!("SERVER_IP");
+static TELEMETRY_PORT: &str = match option_env!("TELEMETRY_PORT") {
+    Some(port) => port,
+    None => "9001",
+};
+
+/// Keep datagrams below the MTU.
+const MAX_DATAGRAM: usize = 1_200;
+const MAX_LINE: usize = 120;
+/// Nothing sent for this long means streaming stopped, the next sample starts with the header.
+const STREAM_GAP: Duration = Duration::from_secs(1);
+
+/// Sends IMU debug samples as CSV lines over UDP, see `ui/scripts/imu-record.js`.
+#[embassy_executor::task]
+pub async fn telemetry_task(stack: Stack<'static>) {
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut rx_buffer = [0; 64];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_buffer = [0; 4 * MAX_DATAGRAM];
+
+    let remote_endpoint = (
+        Ipv4Addr::from_str(SERVER_IP).expect("Invalid server IP"),
+        u16::from_str(TELEMETRY_PORT).expect("Invalid telemetry port"),
+    );
+
+    stack.wait_config_up().await;
+
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    socket.bind(0).unwrap();
+
+    info!("{} Ready", TAG);
+
+    let mut datagram = String::with_capacity(MAX_DATAGRAM);
+    let mut last_sent: Option<Instant> = None;
+
+    loop {
+        // Wait for one sample, then take whatever else is queued
+        let mut next = Some(DEBUG_SAMPLES.receive().await);
+
+        if last_sent.is_none_or(|sent| sent.elapsed() > STREAM_GAP) {
+            datagram.push_str(TRACE_HEADER);
+            datagram.push('\n');
+        }
+        last_sent = Some(Instant::now());
+
+        while let Some(debug) = next {
+            write_trace_line(&mut datagram, &debug.sample, &debug.attitude, debug.gesture).ok();
+
+            if datagram.len() > MAX_DATAGRAM - MAX_LINE {
+                break;
+            }
+            next = DEBUG_SAMPLES.try_receive().ok();
+        }
+
+        if let Err(error) = socket.send_to(datagram.as_bytes(), remote_endpoint).await {
+            warn!("{} Send error: {:?}", TAG, error);
+        }
+        datagram.clear();
+    }
+}
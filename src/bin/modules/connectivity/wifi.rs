@@ -1,14 +1,20 @@
 use defmt::{error, info, warn};
 use embassy_executor::Spawner;
+use embassy_futures::select::{Either, Either3, select, select3};
 use embassy_net::{Runner, Stack, StackResources};
 use embassy_time::{Duration, Timer};
 use esp_alloc as _;
 use esp_hal::{peripherals::WIFI, rng::Rng};
 use esp_radio::wifi::{
-    Config, ControllerConfig, DisconnectedStationInfo, Interface, WifiController, scan::ScanConfig,
-    sta::StationConfig,
+    Config, ControllerConfig, DisconnectedStationInfo, Interface, PowerSaveMode, WifiController,
+    scan::ScanConfig, sta::StationConfig,
 };
 
+use crate::modules::connectivity::clock::{is_clock_set, wait_for_sync};
+use crate::modules::indicator::{Notification, notify};
+use crate::modules::mode::{ModeReceiver, SYSTEM_MODE, SystemMode};
+use crate::modules::power::{POWER_STATE, PowerReceiver, PowerState};
+
 macro_rules! mk_static {
     ($t:ty,$val:expr) => {{
         static STATIC_CELL: static_cell::StaticCell<$t> = static_cell::StaticCell::new();
@@ -22,7 +28,9 @@ static WIFI_SSID: &str = env!("WIFI_SSID");
 static WIFI_PASSWORD: &str = env!("WIFI_PASS");
 const TAG: &str = "[WIFI]";
 
-pub async fn wifi_init(spawner: Spawner, wifi: WIFI<'static>) -> Stack<'static> {
+/// Starts the radio, it only connects while the mode uses the network, or always with
+/// `keep_connected`. Other modes connect until the clock was set.
+pub fn wifi_init(spawner: Spawner, wifi: WIFI<'static>, keep_connected: bool) -> Stack<'static> {
     let station_config = Config::Station(
         StationConfig::default()
             .with_ssid(WIFI_SSID)
@@ -30,7 +38,7 @@ pub async fn wifi_init(spawner: Spawner, wifi: WIFI<'static>) -> Stack<'static>
     );
 
     info!("{} Starting", TAG);
-    let (mut controller, interfaces) = esp_radio::wifi::new(
+    let (controller, interfaces) = esp_radio::wifi::new(
         wifi,
         ControllerConfig::default().with_initial_config(station_config),
     )
@@ -46,47 +54,88 @@ pub async fn wifi_init(spawner: Spawner, wifi: WIFI<'static>) -> Stack<'static>
     let (stack, runner) = embassy_net::new(
         wifi_interface,
         config,
-        mk_static!(StackResources<3>, StackResources::<3>::new()),
+        mk_static!(StackResources<5>, StackResources::<5>::new()),
         seed,
     );
 
-    info!("{} Scanning", TAG);
-    let scan_config = ScanConfig::default().with_max(10);
-    let result = controller.scan_async(&scan_config).await.unwrap();
-    for ap in result {
-        info!("- {} ({})", ap.ssid.as_str(), ap.signal_strength);
-    }
-
-    spawner.spawn(connection(controller).unwrap());
+    spawner.spawn(connection(controller, keep_connected).unwrap());
     spawner.spawn(net_task(runner).unwrap());
 
-    stack.wait_config_up().await;
-
-    if let Some(config) = stack.config_v4() {
-        info!("{} Got IP: {}", TAG, config.address);
-    }
-
     return stack;
 }
 
 #[embassy_executor::task]
-async fn connection(mut controller: WifiController<'static>) {
+async fn connection(mut controller: WifiController<'static>, keep_connected: bool) {
     info!("{} Start connection task", TAG);
+    let mut mode = SYSTEM_MODE.receiver().unwrap();
+    let mut power = POWER_STATE.receiver().unwrap();
+    let mut scanned = false;
+
+    'connect: loop {
+        if !keep_connected {
+            let sleeping = power.get_and(|state| *state == PowerState::Sleeping);
+            if let Either::First(_) = select(sleeping, wait_for_network_use(&mut mode, true)).await
+            {
+                sleep_radio(&mut controller, &mut power).await;
+                continue;
+            }
+        }
+
+        if !scanned {
+            info!("{} Scanning", TAG);
+            let scan_config = ScanConfig::default().with_max(10);
+            let result = controller.scan_async(&scan_config).await.unwrap();
+            for ap in result {
+                info!("- {} ({})", ap.ssid.as_str(), ap.signal_strength);
+            }
+            scanned = true;
+        }
 
-    loop {
         info!("{} About to connect...", TAG);
 
         match controller.connect_async().await {
             Ok(info) => {
                 info!("{} Connected to {:?}", TAG, info.ssid.as_str());
+                if let Some(state) = power.try_get() {
+                    controller.set_power_saving(get_power_saving(state)).ok();
+                }
 
                 fn get_ssid(info: &Option<DisconnectedStationInfo>) -> &str {
                     info.as_ref().map(|info| info.ssid.as_str()).unwrap_or("??")
                 }
 
-                // wait until we're no longer connected
-                let info = controller.wait_for_disconnect_async().await.ok();
-                warn!("{} Disconnected: {:?}", TAG, get_ssid(&info));
+                loop {
+                    // wait until we're no longer connected, or the mode doesn't use the network
+                    let left_network = async {
+                        if keep_connected {
+                            core::future::pending().await
+                        } else {
+                            wait_for_network_use(&mut mode, false).await
+                        }
+                    };
+
+                    let disconnected = controller.wait_for_disconnect_async();
+                    match select3(disconnected, left_network, power.changed()).await {
+                        Either3::First(info) => {
+                            warn!("{} Disconnected: {:?}", TAG, get_ssid(&info.ok()));
+                            notify(Notification::WifiLost);
+                            break;
+                        }
+                        Either3::Second(_) => {
+                            info!("{} Disconnecting, nothing uses the network", TAG);
+                            controller.disconnect_async().await.ok();
+                            continue 'connect;
+                        }
+                        Either3::Third(PowerState::Sleeping) => {
+                            controller.disconnect_async().await.ok();
+                            sleep_radio(&mut controller, &mut power).await;
+                            continue 'connect;
+                        }
+                        Either3::Third(state) => {
+                            controller.set_power_saving(get_power_saving(state)).ok();
+                        }
+                    }
+                }
             }
             Err(e) => {
                 error!("{} Failed to connect: {:?}", TAG, e);
@@ -97,6 +146,34 @@ async fn connection(mut controller: WifiController<'static>) {
     }
 }
 
+/// The radio only naps between beacons while the owl is dozing.
+fn get_power_saving(state: PowerState) -> PowerSaveMode {
+    match state {
+        PowerState::Dozing => PowerSaveMode::Maximum,
+        _ => PowerSaveMode::None,
+    }
+}
+
+/// Turn the radio off until the owl wakes up again.
+async fn sleep_radio(controller: &mut WifiController<'static>, power: &mut PowerReceiver) {
+    info!("{} Stopping the radio to sleep", TAG);
+    controller.stop_async().await.ok();
+    power.get_and(|state| *state != PowerState::Sleeping).await;
+    controller.start_async().await.ok();
+}
+
+/// Wait until the network is needed or not, by the mode or to set the clock.
+async fn wait_for_network_use(mode: &mut ModeReceiver, used: bool) {
+    loop {
+        let current = mode.get().await;
+        let setting_clock = current != SystemMode::Off && !is_clock_set();
+        if (current.is_networked() || setting_clock) == used {
+            return;
+        }
+        select(mode.changed(), wait_for_sync()).await;
+    }
+}
+
 #[embassy_executor::task]
 async fn net_task(mut runner: Runner<'static, Interface<'static>>) {
     runner.run().await
@@ -0,0 +1,327 @@
+use alloc::string::String;
+use core::cell::Cell;
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use defmt::{info, warn};
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embassy_time::{Duration, Instant, Timer};
+use rgb::RGB8;
+
+use crate::modules::connectivity::mqtt::{CRASH_TOPIC, publish_and_confirm};
+use crate::modules::indicator::effects::Effect;
+
+const TAG: &str = "[CRASH]";
+
+/// Crashes in a row after which the owl boots into safe mode.
+const MAX_CRASHES: u8 = 3;
+/// Running this long without crashing resets the count.
+const STABLE_UPTIME: Duration = Duration::from_secs(60);
+
+/// Shown on the indicator in safe mode.
+pub const SAFE_MODE_EFFECT: Effect = Effect::Breathe {
+    color: RGB8::new(255, 0, 255),
+    period: Duration::from_secs(2),
+};
+
+/// "OWLC", marks a record written by this firmware, anything else is left over from power up.
+const MAGIC: u32 = 0x434C_574F;
+const RECORD_SIZE: usize = 256;
+
+// Record layout: magic, checksum, crash count, reason, uptime, lengths of the animation name
+// and message, then both texts
+const HEADER_SIZE: usize = 8;
+const COUNT: usize = 8;
+const REASON: usize = 9;
+const UPTIME: usize = 10;
+const ANIMATION_LENGTH: usize = 18;
+const MESSAGE_LENGTH: usize = 19;
+const ANIMATION: usize = 20;
+const ANIMATION_SIZE: usize = 32;
+const MESSAGE: usize = ANIMATION + ANIMATION_SIZE;
+const MESSAGE_SIZE: usize = RECORD_SIZE - MESSAGE;
+
+// Survives a reset, but not losing power
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut CRASH_RECORD: [u8; RECORD_SIZE] = [0; RECORD_SIZE];
+
+static CURRENT_ANIMATION: Mutex<CriticalSectionRawMutex, Cell<Option<&'static str>>> =
+    Mutex::new(Cell::new(None));
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CrashReason {
+    Panic = 1,
+    /// A supervised task stopped checking in.
+    Watchdog = 2,
+}
+
+impl CrashReason {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            CrashReason::Panic => "panic",
+            CrashReason::Watchdog => "watchdog",
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<CrashReason> {
+        match value {
+            1 => Some(CrashReason::Panic),
+            2 => Some(CrashReason::Watchdog),
+            _ => None,
+        }
+    }
+}
+
+/// What the owl was doing when it crashed.
+pub struct CrashReport {
+    pub reason: CrashReason,
+    pub uptime_ms: u64,
+    animation: [u8; ANIMATION_SIZE],
+    animation_length: usize,
+    /// What went wrong, cut off when too long.
+    message: [u8; MESSAGE_SIZE],
+    message_length: usize,
+}
+
+impl CrashReport {
+    fn encode(&self, record: &mut [u8; RECORD_SIZE]) {
+        record[REASON] = self.reason as u8;
+        record[UPTIME..UPTIME + 8].copy_from_slice(&self.uptime_ms.to_le_bytes());
+        record[ANIMATION_LENGTH] = self.animation_length as u8;
+        record[MESSAGE_LENGTH] = self.message_length as u8;
+        record[ANIMATION..MESSAGE].copy_from_slice(&self.animation);
+        record[MESSAGE..].copy_from_slice(&self.message);
+    }
+
+    fn decode(record: &[u8; RECORD_SIZE]) -> Option<CrashReport> {
+        let mut report = CrashReport {
+            reason: CrashReason::from_u8(record[REASON])?,
+            uptime_ms: u64::from_le_bytes(record[UPTIME..UPTIME + 8].try_into().ok()?),
+            animation: [0; ANIMATION_SIZE],
+            animation_length: (record[ANIMATION_LENGTH] as usize).min(ANIMATION_SIZE),
+            message: [0; MESSAGE_SIZE],
+            message_length: (record[MESSAGE_LENGTH] as usize).min(MESSAGE_SIZE),
+        };
+        report
+            .animation
+            .copy_from_slice(&record[ANIMATION..MESSAGE]);
+        report.message.copy_from_slice(&record[MESSAGE..]);
+
+        Some(report)
+    }
+
+    pub fn get_animation(&self) -> Option<&str> {
+        let animation = as_str(&self.animation[..self.animation_length]);
+        (!animation.is_empty()).then_some(animation)
+    }
+
+    pub fn get_message(&self) -> &str {
+        as_str(&self.message[..self.message_length])
+    }
+
+    fn to_json(&self, crashes: u8) -> String {
+        let mut json = String::new();
+        write!(
+            json,
+            "{{\"reason\":\"{}\",\"uptime_ms\":{},\"crashes\":{},\"animation\":",
+            self.reason.get_name(),
+            self.uptime_ms,
+            crashes
+        )
+        .ok();
+
+        match self.get_animation() {
+            Some(animation) => write!(json, "\"{}\"", animation).ok(),
+            None => write!(json, "null").ok(),
+        };
+
+        json.push_str(",\"message\":\"");
+        for character in self.get_message().chars() {
+            match character {
+                '"' | '\\' => {
+                    json.push('\\');
+                    json.push(character);
+                }
+                character if character.is_control() => json.push(' '),
+                character => json.push(character),
+            }
+        }
+        json.push_str("\"}");
+
+        json
+    }
+}
+
+/// Remember which animation is playing, it ends up in the crash report.
+pub fn set_current_animation(animation: Option<&'static str>) {
+    CURRENT_ANIMATION.lock(|current| current.set(animation));
+}
+
+/// Store the panic in RTC memory, it is published after the reset.
+pub fn record_panic(panic_info: &PanicInfo) {
+    record_crash(CrashReason::Panic, |writer| {
+        if let Some(location) = panic_info.location() {
+            write!(writer, "{}:{}: ", location.file(), location.line()).ok();
+        }
+        write!(writer, "{}", panic_info.message()).ok();
+    });
+}
+
+/// Store the task that starved the watchdog, it is published after the reset.
+pub fn record_watchdog(task: &str, starved_for: Duration) {
+    record_crash(CrashReason::Watchdog, |writer| {
+        write!(writer, "{} starved for {}ms", task, starved_for.as_millis()).ok();
+    });
+}
+
+fn record_crash(reason: CrashReason, write_message: impl FnOnce(&mut SliceWriter)) {
+    let mut message = [0; MESSAGE_SIZE];
+    let mut writer = SliceWriter {
+        buffer: &mut message,
+        position: 0,
+    };
+    write_message(&mut writer);
+    let message_length = writer.position;
+
+    let mut animation = [0; ANIMATION_SIZE];
+    let mut writer = SliceWriter {
+        buffer: &mut animation,
+        position: 0,
+    };
+    if let Some(name) = CURRENT_ANIMATION.lock(|current| current.get()) {
+        writer.write_str(name).ok();
+    }
+    let animation_length = writer.position;
+
+    let report = CrashReport {
+        reason,
+        uptime_ms: Instant::now().as_millis(),
+        animation,
+        animation_length,
+        message,
+        message_length,
+    };
+
+    let mut record = read_record();
+    report.encode(&mut record);
+    record[COUNT] = record[COUNT].saturating_add(1);
+    write_record(record);
+}
+
+/// Check the record left by the last boot.
+///
+/// Returns `true` when the owl keeps crashing and should boot into safe mode. Safe mode
+/// clears the count, so the next reset tries a normal boot again.
+pub fn crash_init() -> bool {
+    let mut record = read_record();
+    let crashes = record[COUNT];
+
+    if let Some(report) = CrashReport::decode(&record) {
+        warn!(
+            "{} Crashed after {}ms ({}/{}): {}",
+            TAG,
+            report.uptime_ms,
+            crashes,
+            MAX_CRASHES,
+            report.get_message()
+        );
+    }
+
+    let safe_mode = crashes >= MAX_CRASHES;
+    if safe_mode {
+        warn!("{} Crashing in a loop, starting in safe mode", TAG);
+        record[COUNT] = 0;
+        write_record(record);
+    }
+
+    safe_mode
+}
+
+/// Queue the report of the last crash on MQTT, it is kept until it was published.
+pub fn publish_crash_report() {
+    let record = read_record();
+    let Some(report) = CrashReport::decode(&record) else {
+        return;
+    };
+
+    info!("{} Publishing crash report", TAG);
+    publish_and_confirm(
+        CRASH_TOPIC,
+        report.to_json(record[COUNT]),
+        true,
+        clear_crash_report,
+    );
+}
+
+fn clear_crash_report() {
+    info!("{} Crash report published", TAG);
+    let mut record = read_record();
+    record[REASON] = 0;
+    write_record(record);
+}
+
+/// Forget the crashes once the owl has been running for a while.
+#[embassy_executor::task]
+pub async fn crash_guard_task() {
+    Timer::after(STABLE_UPTIME).await;
+
+    let mut record = read_record();
+    if record[COUNT] > 0 {
+        info!("{} Running stable, resetting crash count", TAG);
+        record[COUNT] = 0;
+        write_record(record);
+    }
+}
+
+fn read_record() -> [u8; RECORD_SIZE] {
+    let record = unsafe { CRASH_RECORD };
+
+    let magic = u32::from_le_bytes([record[0], record[1], record[2], record[3]]);
+    let checksum_stored = u32::from_le_bytes([record[4], record[5], record[6], record[7]]);
+
+    if magic != MAGIC || checksum(&record[HEADER_SIZE..]) != checksum_stored {
+        // Powered up, RTC memory holds noise
+        let mut record = [0; RECORD_SIZE];
+        record[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        return record;
+    }
+
+    record
+}
+
+fn write_record(mut record: [u8; RECORD_SIZE]) {
+    let checksum = checksum(&record[HEADER_SIZE..]);
+    record[4..8].copy_from_slice(&checksum.to_le_bytes());
+    unsafe { CRASH_RECORD = record };
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    // FNV-1a
+    data.iter().fold(0x811C_9DC5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Text cut off in the middle of a character keeps the part before it.
+fn as_str(bytes: &[u8]) -> &str {
+    match core::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(error) => core::str::from_utf8(&bytes[..error.valid_up_to()]).unwrap_or(""),
+    }
+}
+
+/// Formats into a fixed buffer without allocating, the heap may be what broke.
+struct SliceWriter<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl Write for SliceWriter<'_> {
+    fn write_str(&mut self, text: &str) -> core::fmt::Result {
+        let length = text.len().min(self.buffer.len() - self.position);
+        self.buffer[self.position..self.position + length]
+            .copy_from_slice(&text.as_bytes()[..length]);
+        self.position += length;
+        Ok(())
+    }
+}
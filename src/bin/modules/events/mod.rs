@@ -0,0 +1,193 @@
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt::Write;
+
+use defmt::{info, warn};
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    pubsub::{PubSubChannel, WaitResult},
+};
+use embassy_time::Instant;
+use esp_hal::rng::Rng;
+
+use crate::modules::{
+    audio::{AUDIO_QUEUE, toggle_mute},
+    connectivity::mqtt::{EVENTS_TOPIC, publish},
+    indicator::{Notification, notify},
+    interaction::button::ButtonEvent,
+    mode::get_mode,
+    motion::{CALIBRATE_TRIGGER, gesture::Gesture},
+    quiet::toggle_quiet,
+    servo::{
+        animation::{ANIMATION_QUEUE, AnimationRequest},
+        animations::AnimationType,
+    },
+    touch::detector::TouchEvent,
+};
+
+use rules::{Action, RANDOM_ANIMATIONS, RULES, Rule, Trigger};
+
+pub mod rules;
+
+const TAG: &str = "[EVENTS]";
+
+/// Events kept for a subscriber that is busy, older ones are dropped.
+const EVENT_CAPACITY: usize = 8;
+/// The rules task.
+const EVENT_SUBSCRIBERS: usize = 1;
+
+/// Everything the owl notices, reactions are picked by [`rules::RULES`].
+pub static EVENT_BUS: PubSubChannel<
+    CriticalSectionRawMutex,
+    Event,
+    EVENT_CAPACITY,
+    EVENT_SUBSCRIBERS,
+    0,
+> = PubSubChannel::new();
+
+#[derive(Clone, Copy)]
+pub enum Event {
+    BeakPressed(ButtonEvent),
+    Touched(TouchEvent),
+    /// Includes being picked up.
+    Gesture(Gesture),
+    /// An animation asked for over MQTT.
+    MqttCommand(AnimationType),
+    StreamStarted,
+}
+
+impl Event {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Event::BeakPressed(_) => "beak",
+            Event::Touched(_) => "touch",
+            Event::Gesture(_) => "gesture",
+            Event::MqttCommand(_) => "mqtt_command",
+            Event::StreamStarted => "stream_started",
+        }
+    }
+
+    fn get_trigger(&self) -> Trigger {
+        match *self {
+            Event::BeakPressed(button) => Trigger::Beak(button),
+            Event::Touched(touch) => Trigger::Touch(touch),
+            Event::Gesture(gesture) => Trigger::Gesture(gesture),
+            Event::MqttCommand(_) => Trigger::MqttCommand,
+            Event::StreamStarted => Trigger::StreamStarted,
+        }
+    }
+
+    /// {"event":"touch","type":"long touch","pad":"left wing"}
+    fn to_json(&self) -> String {
+        let mut json = String::new();
+        write!(json, "{{\"event\":\"{}\"", self.get_name()).ok();
+
+        match self {
+            Event::BeakPressed(button) => {
+                write!(json, ",\"type\":\"{}\"", button.get_name()).ok();
+            }
+            Event::Touched(touch) => {
+                write!(json, ",\"type\":\"{}\"", touch.get_name()).ok();
+                if let Some(pad) = touch.get_pad() {
+                    write!(json, ",\"pad\":\"{}\"", pad.get_name()).ok();
+                }
+            }
+            Event::Gesture(gesture) => {
+                write!(json, ",\"type\":\"{}\"", gesture.get_name()).ok();
+            }
+            Event::MqttCommand(animation) => {
+                write!(json, ",\"animation\":\"{}\"", animation.get_name()).ok();
+            }
+            Event::StreamStarted => (),
+        }
+
+        json.push('}');
+        json
+    }
+}
+
+/// Never waits, a subscriber that fell behind misses the oldest events.
+pub fn publish_event(event: Event) {
+    EVENT_BUS.immediate_publisher().publish_immediate(event);
+}
+
+/// Runs the rules for every event and mirrors the events to MQTT.
+#[embassy_executor::task]
+pub async fn events_task() {
+    info!("{} task started", TAG);
+    let mut subscriber = EVENT_BUS.subscriber().unwrap();
+    let mut last_runs: Vec<Option<Instant>> = vec![None; RULES.len()];
+
+    loop {
+        let event = match subscriber.next_message().await {
+            WaitResult::Message(event) => event,
+            WaitResult::Lagged(missed) => {
+                warn!("{} Missed {} events", TAG, missed);
+                continue;
+            }
+        };
+
+        let mode = get_mode();
+        if mode.is_networked() {
+            publish(EVENTS_TOPIC, event.to_json(), false);
+        }
+
+        let trigger = event.get_trigger();
+        let now = Instant::now();
+
+        for (rule, last_run) in RULES.iter().zip(last_runs.iter_mut()) {
+            if rule.trigger != trigger || !rule.modes.contains(&mode) {
+                continue;
+            }
+
+            if last_run.is_some_and(|last| last + rule.cooldown > now) {
+                continue;
+            }
+
+            *last_run = Some(now);
+            run_action(rule, &event).await;
+        }
+    }
+}
+
+async fn run_action(rule: &Rule, event: &Event) {
+    let request = |animation| {
+        if rule.urgent {
+            AnimationRequest::urgent(animation)
+        } else {
+            AnimationRequest::new(animation)
+        }
+    };
+
+    match rule.action {
+        Action::Play(animation) => ANIMATION_QUEUE.send(request(animation)).await,
+        Action::PlayRandom => {
+            let index = Rng::new().random() as usize % RANDOM_ANIMATIONS.len();
+            let animation = RANDOM_ANIMATIONS[index];
+            ANIMATION_QUEUE.send(request(animation)).await;
+        }
+        Action::PlayRequested => {
+            if let Event::MqttCommand(animation) = event {
+                ANIMATION_QUEUE.send(request(*animation)).await;
+            }
+        }
+        Action::Track(track) => AUDIO_QUEUE.signal(track),
+        Action::Notify(notification) => notify(notification),
+        Action::Publish { topic, payload } => publish(topic, String::from(payload), false),
+        Action::ToggleQuiet => {
+            toggle_quiet();
+        }
+        Action::ToggleMute => {
+            if toggle_mute() {
+                info!("{} Muted", TAG);
+                notify(Notification::Muted);
+            } else {
+                info!("{} Unmuted", TAG);
+                notify(Notification::Unmuted);
+            }
+        }
+        Action::Calibrate => {
+            info!("{} Calibrating", TAG);
+            CALIBRATE_TRIGGER.signal(());
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use embassy_time::Duration;
+
+use crate::modules::{
+    audio::tracks::Tracks,
+    indicator::Notification,
+    interaction::button::ButtonEvent,
+    mode::SystemMode,
+    motion::gesture::Gesture,
+    servo::animations::AnimationType,
+    touch::detector::{TouchEvent, TouchPad},
+};
+
+/// Don't interrupt an animation started by the same rule a moment ago.
+const ANIMATION_COOLDOWN: Duration = Duration::from_secs(2);
+const NO_COOLDOWN: Duration = Duration::from_ticks(0);
+
+// Hybrid mode does what both others do
+const PLAY: &[SystemMode] = &[SystemMode::Play, SystemMode::Hybrid];
+const MAILBOX: &[SystemMode] = &[SystemMode::Mailbox, SystemMode::Hybrid];
+const ALL_MODES: &[SystemMode] = &[SystemMode::Play, SystemMode::Mailbox, SystemMode::Hybrid];
+
+/// Played by [`Action::PlayRandom`].
+pub const RANDOM_ANIMATIONS: [AnimationType; 6] = [
+    AnimationType::Yap,
+    AnimationType::Hello,
+    AnimationType::Content,
+    AnimationType::Shocked,
+    AnimationType::PickedUp,
+    AnimationType::Panic,
+];
+
+/// What a rule reacts to, events without what they carry.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Trigger {
+    Beak(ButtonEvent),
+    Touch(TouchEvent),
+    Gesture(Gesture),
+    MqttCommand,
+    StreamStarted,
+}
+
+#[derive(Clone, Copy)]
+pub enum Action {
+    Play(AnimationType),
+    PlayRandom,
+    /// Play the animation the event asked for.
+    PlayRequested,
+    Track(Tracks),
+    Notify(Notification),
+    Publish {
+        topic: &'static str,
+        payload: &'static str,
+    },
+    ToggleMute,
+    ToggleQuiet,
+    /// Sample the resting motion sensor again, like holding the beak at boot.
+    Calibrate,
+}
+
+pub struct Rule {
+    pub trigger: Trigger,
+    pub modes: &'static [SystemMode],
+    pub action: Action,
+    /// Minimum time between two runs of the rule.
+    pub cooldown: Duration,
+    /// Plays in quiet mode too, for animations someone asked for on purpose.
+    pub urgent: bool,
+}
+
+const fn rule(
+    trigger: Trigger,
+    modes: &'static [SystemMode],
+    action: Action,
+    cooldown: Duration,
+) -> Rule {
+    Rule {
+        trigger,
+        modes,
+        action,
+        cooldown,
+        urgent: false,
+    }
+}
+
+impl Rule {
+    const fn urgent(self) -> Rule {
+        Rule {
+            urgent: true,
+            ..self
+        }
+    }
+}
+
+/// Every rule that matches an event runs, in order.
+pub static RULES: &[Rule] = &[
+    // Beak
+    rule(
+        Trigger::Beak(ButtonEvent::Click),
+        ALL_MODES,
+        Action::Play(AnimationType::Yap),
+        NO_COOLDOWN,
+    )
+    .urgent(),
+    rule(
+        Trigger::Beak(ButtonEvent::Click),
+        MAILBOX,
+        Action::Publish {
+            topic: "owlimatronic/mailbox/ack",
+            payload: "read",
+        },
+        NO_COOLDOWN,
+    ),
+    rule(
+        Trigger::Beak(ButtonEvent::DoubleClick),
+        PLAY,
+        Action::PlayRandom,
+        NO_COOLDOWN,
+    )
+    .urgent(),
+    rule(
+        Trigger::Beak(ButtonEvent::LongPress),
+        ALL_MODES,
+        Action::ToggleMute,
+        NO_COOLDOWN,
+    ),
+    rule(
+        Trigger::Beak(ButtonEvent::HoldRepeat),
+        ALL_MODES,
+        Action::Notify(Notification::Held),
+        NO_COOLDOWN,
+    ),
+    rule(
+        Trigger::Beak(ButtonEvent::VeryLongPress),
+        ALL_MODES,
+        Action::Calibrate,
+        NO_COOLDOWN,
+    ),
+    // Touch
+    rule(
+        Trigger::Touch(TouchEvent::Stroke),
+        PLAY,
+        Action::Play(AnimationType::Content),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Touch(TouchEvent::Hug),
+        ALL_MODES,
+        Action::ToggleQuiet,
+        NO_COOLDOWN,
+    ),
+    rule(
+        Trigger::Touch(TouchEvent::Touch(TouchPad::HeadFront)),
+        PLAY,
+        Action::Play(AnimationType::Yap),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Touch(TouchEvent::Touch(TouchPad::HeadBack)),
+        PLAY,
+        Action::Play(AnimationType::Yap),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Touch(TouchEvent::LongTouch(TouchPad::HeadFront)),
+        PLAY,
+        Action::Play(AnimationType::Content),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Touch(TouchEvent::LongTouch(TouchPad::HeadBack)),
+        PLAY,
+        Action::Play(AnimationType::Content),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Touch(TouchEvent::Touch(TouchPad::WingLeft)),
+        PLAY,
+        Action::Play(AnimationType::Hello),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Touch(TouchEvent::Touch(TouchPad::WingRight)),
+        PLAY,
+        Action::Play(AnimationType::Hello),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Touch(TouchEvent::LongTouch(TouchPad::WingLeft)),
+        PLAY,
+        Action::Play(AnimationType::Shocked),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Touch(TouchEvent::LongTouch(TouchPad::WingRight)),
+        PLAY,
+        Action::Play(AnimationType::Shocked),
+        ANIMATION_COOLDOWN,
+    ),
+    // Motion
+    rule(
+        Trigger::Gesture(Gesture::PickedUp),
+        PLAY,
+        Action::Play(AnimationType::PickedUp),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Gesture(Gesture::SetDown),
+        PLAY,
+        Action::Track(Tracks::BuboRatched2),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Gesture(Gesture::Shake),
+        PLAY,
+        Action::Play(AnimationType::Panic),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Gesture(Gesture::Tap),
+        PLAY,
+        Action::Play(AnimationType::Yap),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Gesture(Gesture::DoubleTap),
+        PLAY,
+        Action::Play(AnimationType::Hello),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Gesture(Gesture::UpsideDown),
+        PLAY,
+        Action::Play(AnimationType::Shocked),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Gesture(Gesture::TiltLeft),
+        PLAY,
+        Action::Play(AnimationType::LeanLeft),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Gesture(Gesture::TiltRight),
+        PLAY,
+        Action::Play(AnimationType::LeanRight),
+        ANIMATION_COOLDOWN,
+    ),
+    rule(
+        Trigger::Gesture(Gesture::Spin),
+        PLAY,
+        Action::Play(AnimationType::Sweep),
+        ANIMATION_COOLDOWN,
+    ),
+    // Remote
+    rule(
+        Trigger::MqttCommand,
+        ALL_MODES,
+        Action::PlayRequested,
+        NO_COOLDOWN,
+    )
+    .urgent(),
+    rule(
+        Trigger::StreamStarted,
+        ALL_MODES,
+        Action::Notify(Notification::StreamStarted),
+        NO_COOLDOWN,
+    ),
+];
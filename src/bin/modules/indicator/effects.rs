@@ -0,0 +1,162 @@
+use core::f32::consts::PI;
+
+use embassy_time::Duration;
+use rgb::RGB8;
+
+// Blink codes flash the code a number of times, then pause before repeating
+const CODE_ON: Duration = Duration::from_millis(200);
+const CODE_OFF: Duration = Duration::from_millis(300);
+const CODE_PAUSE: Duration = Duration::from_millis(1_500);
+
+const PARTY_COLORS: [RGB8; 3] = [
+    RGB8::new(255, 0, 80),
+    RGB8::new(0, 200, 255),
+    RGB8::new(255, 200, 0),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Effect {
+    Solid(RGB8),
+    /// On for the first half of every period.
+    Blink {
+        color: RGB8,
+        period: Duration,
+    },
+    /// Fades in and out once per period.
+    Breathe {
+        color: RGB8,
+        period: Duration,
+    },
+    /// Stays at `to` once done.
+    Fade {
+        from: RGB8,
+        to: RGB8,
+        duration: Duration,
+    },
+    /// Goes around the color wheel once per period.
+    Rainbow {
+        period: Duration,
+    },
+    /// Fades from one color to the next, each taking a period.
+    ColorCycle {
+        colors: &'static [RGB8],
+        period: Duration,
+    },
+    /// Blinks `code` times, then pauses, so errors can be told apart.
+    BlinkCode {
+        color: RGB8,
+        code: u8,
+    },
+}
+
+impl Effect {
+    /// Presets that can be picked over MQTT.
+    pub fn get_from_binary(payload: &[u8]) -> Option<Effect> {
+        match payload {
+            b"rainbow" => Some(Effect::Rainbow {
+                period: Duration::from_secs(5),
+            }),
+            b"party" => Some(Effect::ColorCycle {
+                colors: &PARTY_COLORS,
+                period: Duration::from_millis(600),
+            }),
+            b"breathe" => Some(Effect::Breathe {
+                color: RGB8::new(255, 255, 255),
+                period: Duration::from_secs(4),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Solid effects and finished fades are only drawn again when something changes.
+    pub fn is_animated(&self, elapsed: Duration) -> bool {
+        match *self {
+            Effect::Solid(_) => false,
+            Effect::Fade { duration, .. } => elapsed < duration,
+            _ => true,
+        }
+    }
+
+    pub fn get_color(&self, elapsed: Duration) -> RGB8 {
+        let elapsed_ms = elapsed.as_millis();
+
+        match *self {
+            Effect::Solid(color) => color,
+            Effect::Blink { color, period } => {
+                let phase = elapsed_ms % period.as_millis().max(1);
+                if phase < period.as_millis() / 2 {
+                    color
+                } else {
+                    RGB8::default()
+                }
+            }
+            Effect::Breathe { color, period } => {
+                let progress = cycle_progress(elapsed, period);
+                scale(color, (1.0 - libm::cosf(progress * 2.0 * PI)) / 2.0)
+            }
+            Effect::Fade { from, to, duration } => {
+                let progress = elapsed_ms as f32 / duration.as_millis().max(1) as f32;
+                blend(from, to, progress.min(1.0))
+            }
+            Effect::Rainbow { period } => hue_to_rgb(cycle_progress(elapsed, period)),
+            Effect::ColorCycle { colors, period } => {
+                if colors.is_empty() {
+                    return RGB8::default();
+                }
+
+                let index = (elapsed_ms / period.as_millis().max(1)) as usize;
+                let from = colors[index % colors.len()];
+                let to = colors[(index + 1) % colors.len()];
+                blend(from, to, cycle_progress(elapsed, period))
+            }
+            Effect::BlinkCode { color, code } => {
+                let blink = (CODE_ON + CODE_OFF).as_millis();
+                let phase = elapsed_ms % (blink * code as u64 + CODE_PAUSE.as_millis());
+
+                if phase < blink * code as u64 && phase % blink < CODE_ON.as_millis() {
+                    color
+                } else {
+                    RGB8::default()
+                }
+            }
+        }
+    }
+}
+
+/// Position within the current period, from 0 to 1.
+fn cycle_progress(elapsed: Duration, period: Duration) -> f32 {
+    let period_ms = period.as_millis().max(1);
+    (elapsed.as_millis() % period_ms) as f32 / period_ms as f32
+}
+
+fn scale(color: RGB8, factor: f32) -> RGB8 {
+    let channel = |value: u8| (value as f32 * factor.clamp(0.0, 1.0)) as u8;
+    RGB8::new(channel(color.r), channel(color.g), channel(color.b))
+}
+
+fn blend(from: RGB8, to: RGB8, progress: f32) -> RGB8 {
+    let channel = |from: u8, to: u8| {
+        (from as f32 + (to as f32 - from as f32) * progress.clamp(0.0, 1.0)) as u8
+    };
+    RGB8::new(
+        channel(from.r, to.r),
+        channel(from.g, to.g),
+        channel(from.b, to.b),
+    )
+}
+
+/// Fully saturated color, `hue` goes from 0 to 1 around the color wheel.
+fn hue_to_rgb(hue: f32) -> RGB8 {
+    let sector = hue * 6.0;
+    let rising = ((sector % 1.0) * 255.0) as u8;
+    let falling = 255 - rising;
+
+    match sector as u8 {
+        0 => RGB8::new(255, rising, 0),
+        1 => RGB8::new(falling, 255, 0),
+        2 => RGB8::new(0, 255, rising),
+        3 => RGB8::new(0, falling, 255),
+        4 => RGB8::new(rising, 0, 255),
+        _ => RGB8::new(255, 0, falling),
+    }
+}
@@ -1,20 +1,231 @@
-use controller::Indicator;
-use defmt::info;
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
-use esp_hal::{gpio::AnyPin, peripherals::RMT};
+use core::cell::Cell;
+
+use defmt::{info, warn};
+use effects::Effect;
+use embassy_futures::select::select;
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    signal::Signal,
+};
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::{Async, gpio::AnyPin, rmt::ChannelCreator};
 pub use rgb::RGB8;
 
-pub mod controller;
+use crate::modules::{
+    leds::driver::{ColorOrder, LedStrip, StripConfig},
+    quiet::is_quiet,
+};
+
+pub mod effects;
+
+const TAG: &str = "[INDICATOR]";
+
+const STRIP_CONFIG: StripConfig = StripConfig {
+    // The onboard LED takes RGB, unlike most WS2812
+    order: ColorOrder::Rgb,
+    brightness: 255,
+    gamma: 2.2,
+    memory_blocks: 1,
+};
+
+/// Time between two updates of an animated effect.
+const FRAME_DURATION: Duration = Duration::from_millis(20);
 
-pub static INDICATOR_QUEUE: Signal<CriticalSectionRawMutex, RGB8> = Signal::new();
+const PRIORITY_COUNT: usize = 4;
+
+static INDICATOR_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+static EFFECTS: Mutex<CriticalSectionRawMutex, Cell<[Option<ActiveEffect>; PRIORITY_COUNT]>> =
+    Mutex::new(Cell::new([None; PRIORITY_COUNT]));
+
+/// The indicator shows the effect with the highest priority, the others come back once it
+/// is cleared.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Priority {
+    /// Color of the system mode.
+    Base = 0,
+    /// Picked over MQTT.
+    Remote = 1,
+    /// Something is wrong, until it is fixed.
+    Status = 2,
+    /// Short notifications, see [`notify`].
+    Notification = 3,
+}
+
+/// Shown for a moment over everything else.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Notification {
+    MqttConnected,
+    WifiLost,
+    Muted,
+    Unmuted,
+    QuietOn,
+    QuietOff,
+    StreamStarted,
+    /// The beak is still held, one tick for every hold repeat.
+    Held,
+}
+
+impl Notification {
+    fn get_effect(&self) -> Effect {
+        match self {
+            Notification::MqttConnected => Effect::Blink {
+                color: RGB8::new(0, 255, 0),
+                period: Duration::from_millis(250),
+            },
+            Notification::WifiLost => Effect::Blink {
+                color: RGB8::new(255, 120, 0),
+                period: Duration::from_millis(500),
+            },
+            Notification::Muted => Effect::Fade {
+                from: RGB8::new(255, 255, 255),
+                to: RGB8::new(0, 0, 0),
+                duration: Duration::from_millis(800),
+            },
+            Notification::QuietOn => Effect::Fade {
+                from: RGB8::new(0, 0, 120),
+                to: RGB8::new(0, 0, 0),
+                duration: Duration::from_millis(1_500),
+            },
+            Notification::QuietOff => Effect::Fade {
+                from: RGB8::new(0, 0, 0),
+                to: RGB8::new(0, 0, 120),
+                duration: Duration::from_millis(1_500),
+            },
+            Notification::StreamStarted => Effect::Breathe {
+                color: RGB8::new(0, 120, 255),
+                period: Duration::from_millis(750),
+            },
+            Notification::Unmuted => Effect::Fade {
+                from: RGB8::new(0, 0, 0),
+                to: RGB8::new(255, 255, 255),
+                duration: Duration::from_millis(800),
+            },
+            Notification::Held => Effect::Fade {
+                from: RGB8::new(255, 255, 255),
+                to: RGB8::new(0, 0, 0),
+                duration: Duration::from_millis(200),
+            },
+        }
+    }
+
+    fn get_duration(&self) -> Duration {
+        match self {
+            Notification::MqttConnected => Duration::from_secs(1),
+            Notification::WifiLost => Duration::from_secs(3),
+            Notification::Muted | Notification::Unmuted => Duration::from_secs(1),
+            Notification::QuietOn | Notification::QuietOff => Duration::from_millis(1_500),
+            Notification::StreamStarted => Duration::from_millis(1_500),
+            Notification::Held => Duration::from_millis(200),
+        }
+    }
+
+    /// Answers something done on purpose, so it is shown in quiet mode too.
+    fn is_feedback(&self) -> bool {
+        matches!(
+            self,
+            Notification::Muted
+                | Notification::Unmuted
+                | Notification::QuietOn
+                | Notification::QuietOff
+                | Notification::Held
+        )
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ActiveEffect {
+    effect: Effect,
+    started: Instant,
+    until: Option<Instant>,
+}
+
+fn set_slot(priority: Priority, effect: Option<ActiveEffect>) {
+    EFFECTS.lock(|effects| {
+        let mut slots = effects.get();
+        slots[priority as usize] = effect;
+        effects.set(slots);
+    });
+    INDICATOR_CHANGED.signal(());
+}
+
+/// Show `effect` until it is replaced or cleared.
+pub fn set_effect(priority: Priority, effect: Effect) {
+    let active = ActiveEffect {
+        effect,
+        started: Instant::now(),
+        until: None,
+    };
+    set_slot(priority, Some(active));
+}
+
+pub fn clear_effect(priority: Priority) {
+    set_slot(priority, None);
+}
+
+pub fn notify(notification: Notification) {
+    if is_quiet() && !notification.is_feedback() {
+        return;
+    }
+
+    let now = Instant::now();
+    let active = ActiveEffect {
+        effect: notification.get_effect(),
+        started: now,
+        until: Some(now + notification.get_duration()),
+    };
+    set_slot(Priority::Notification, Some(active));
+}
+
+/// Drop expired effects, returns the one to show and when the next one expires.
+fn update_effects(now: Instant) -> (Option<ActiveEffect>, Option<Instant>) {
+    EFFECTS.lock(|effects| {
+        let mut slots = effects.get();
+        for slot in slots.iter_mut() {
+            if slot.is_some_and(|active| active.until.is_some_and(|until| until <= now)) {
+                *slot = None;
+            }
+        }
+        effects.set(slots);
+
+        let current = slots.iter().rev().find_map(|slot| *slot);
+        let next_expiry = slots.iter().filter_map(|slot| slot?.until).min();
+        (current, next_expiry)
+    })
+}
 
 #[embassy_executor::task]
-pub async fn indicator_task(rmt: RMT<'static>, led_pin: AnyPin<'static>) {
-    info!("Indicator task started");
-    let mut indicator = Indicator::new(rmt, led_pin, RGB8::new(0, 0, 0));
+pub async fn indicator_task(channel: ChannelCreator<'static, Async, 3>, led_pin: AnyPin<'static>) {
+    info!("{} task started", TAG);
+    let mut indicator = LedStrip::new(channel, led_pin, STRIP_CONFIG);
+    let mut shown = RGB8::new(0, 0, 0);
+    if let Err(error) = indicator.write(&[shown]).await {
+        warn!("{} Failed to write pixel: {:?}", TAG, error);
+    }
 
     loop {
-        let value = INDICATOR_QUEUE.wait().await;
-        indicator = indicator.set_pixel(value);
+        let now = Instant::now();
+        let (current, next_expiry) = update_effects(now);
+
+        let color = current
+            .map(|active| active.effect.get_color(now - active.started))
+            .unwrap_or_default();
+        if color != shown {
+            if let Err(error) = indicator.write(&[color]).await {
+                warn!("{} Failed to write pixel: {:?}", TAG, error);
+            }
+            shown = color;
+        }
+
+        let next_frame = current
+            .filter(|active| active.effect.is_animated(now - active.started))
+            .map(|_| now + FRAME_DURATION);
+
+        match next_frame.into_iter().chain(next_expiry).min() {
+            Some(wake) => {
+                select(INDICATOR_CHANGED.wait(), Timer::at(wake)).await;
+            }
+            None => INDICATOR_CHANGED.wait().await,
+        }
     }
 }
@@ -0,0 +1,13 @@
+use embassy_time::Duration;
+
+use super::button::ButtonTiming;
+
+/// Time the beak has to stay pressed or released after an edge to count.
+pub const DEBOUNCE: Duration = Duration::from_millis(30);
+
+pub const BEAK_TIMING: ButtonTiming = ButtonTiming {
+    double_click_ms: 300,
+    long_press_ms: 800,
+    repeat_ms: 500,
+    very_long_press_ms: 5_000,
+};
@@ -0,0 +1,78 @@
+use defmt::info;
+use embassy_futures::select::{Either3, select3};
+use embassy_time::{Instant, Timer};
+use esp_hal::gpio::{AnyPin, Event, Input, InputConfig, Pull, WakeEvent};
+
+use crate::modules::{
+    events::{self, publish_event},
+    motion::CALIBRATE_TRIGGER,
+    power::{POWER_STATE, PowerState, report_activity},
+};
+
+use button::Button;
+use config::{BEAK_TIMING, DEBOUNCE};
+
+pub mod config;
+
+pub use owlimatronic_logic::interaction::button;
+
+const TAG: &str = "[INTERACTION]";
+
+#[embassy_executor::task]
+pub async fn interaction_task(beak_pin: AnyPin<'static>) {
+    info!("{} interaction task started", TAG);
+    let input_button_cfg = InputConfig::default().with_pull(Pull::Up);
+
+    // Beak
+    let mut beak_button = Input::new(beak_pin, input_button_cfg);
+    beak_button.listen(Event::AnyEdge);
+
+    // Holding the beak while booting calibrates the motion sensor
+    if beak_button.is_low() {
+        info!("{} Beak held at boot, calibrating", TAG);
+        CALIBRATE_TRIGGER.signal(());
+        beak_button.wait_for_high().await;
+    }
+
+    let mut power_state = POWER_STATE.receiver().unwrap();
+    let mut beak = Button::new(BEAK_TIMING);
+
+    loop {
+        let deadline = match beak.get_deadline() {
+            Some(deadline_ms) => Timer::at(Instant::from_millis(deadline_ms)),
+            None => Timer::at(Instant::MAX),
+        };
+
+        let changed = beak_button.wait_for_any_edge();
+        match select3(changed, deadline, power_state.changed()).await {
+            Either3::First(_) => Timer::after(DEBOUNCE).await,
+            Either3::Second(_) => (),
+            Either3::Third(PowerState::Sleeping) => {
+                // Pressing the beak wakes the owl
+                beak_button
+                    .wakeup_enable(true, WakeEvent::LowLevel)
+                    .unwrap();
+                power_state
+                    .changed_and(|state| *state == PowerState::Awake)
+                    .await;
+                beak_button
+                    .wakeup_enable(false, WakeEvent::LowLevel)
+                    .unwrap();
+                // The press that woke the owl isn't a click
+                beak_button.wait_for_high().await;
+                beak = Button::new(BEAK_TIMING);
+                continue;
+            }
+            Either3::Third(_) => continue,
+        }
+
+        let now = Instant::now().as_millis();
+        let Some(event) = beak.update(now, beak_button.is_low()) else {
+            continue;
+        };
+
+        info!("{} Beak: {}", TAG, event.get_name());
+        report_activity();
+        publish_event(events::Event::BeakPressed(event));
+    }
+}
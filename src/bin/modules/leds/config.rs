@@ -0,0 +1,21 @@
+use core::ops::Range;
+
+use super::driver::{ColorOrder, StripConfig};
+
+/// Both eyes, then the chest.
+pub const PIXEL_COUNT: usize = 5;
+pub const EYE_PIXELS: Range<usize> = 0..2;
+pub const CHEST_PIXELS: Range<usize> = 2..5;
+
+pub const STRIP_CONFIG: StripConfig = StripConfig {
+    order: ColorOrder::Grb,
+    brightness: 160,
+    gamma: 2.8,
+    // Blocks 0 to 2, the indicator uses block 3
+    memory_blocks: 3,
+};
+
+const _: () = assert!(
+    PIXEL_COUNT <= STRIP_CONFIG.get_capacity(),
+    "the pixels don't fit in the RMT memory of the strip"
+);
@@ -0,0 +1,176 @@
+use core::time::Duration;
+
+use esp_hal::{
+    Async,
+    gpio::{AnyPin, Level},
+    rmt::{self, Channel, PulseCode, Tx, TxChannelConfig, TxChannelCreator},
+    time::Rate,
+};
+use rgb::RGB8;
+
+pub const CLOCK_RATE: Rate = Rate::from_mhz(80);
+const CLOCK_DIVIDER: u8 = 2;
+const TICKS_PER_HZ: Rate = Rate::from_mhz(CLOCK_RATE.as_mhz() / CLOCK_DIVIDER as u32);
+
+/// Pulse codes that fit in one block of RMT memory.
+const BLOCK_SIZE: usize = 48;
+/// Enough for a transmit that uses every block.
+const MAX_PULSES: usize = 4 * BLOCK_SIZE;
+
+/// Order the LEDs expect the channels in, most WS2812 are GRB.
+#[allow(dead_code, reason = "picked per strip in its config")]
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorOrder {
+    Rgb,
+    Grb,
+    /// SK6812 with a white LED, white is taken out of the color.
+    Grbw,
+    Rgbw,
+}
+
+impl ColorOrder {
+    const fn get_channel_count(&self) -> usize {
+        match self {
+            ColorOrder::Rgb | ColorOrder::Grb => 3,
+            ColorOrder::Grbw | ColorOrder::Rgbw => 4,
+        }
+    }
+
+    fn get_channels(&self, color: RGB8) -> [u8; 4] {
+        let RGB8 { r, g, b } = color;
+        let white = r.min(g).min(b);
+
+        match self {
+            ColorOrder::Rgb => [r, g, b, 0],
+            ColorOrder::Grb => [g, r, b, 0],
+            ColorOrder::Grbw => [g - white, r - white, b - white, white],
+            ColorOrder::Rgbw => [r - white, g - white, b - white, white],
+        }
+    }
+}
+
+pub struct StripConfig {
+    pub order: ColorOrder,
+    /// Scales every color, 255 is full brightness.
+    pub brightness: u8,
+    /// LEDs look much brighter than their duty cycle, the gamma makes fades look even.
+    pub gamma: f32,
+    /// Blocks of RMT memory for the channel, the following channels can't use them.
+    pub memory_blocks: u8,
+}
+
+impl StripConfig {
+    /// Most pixels that can be sent at once, a transmit has to fit in the channel's memory.
+    pub const fn get_capacity(&self) -> usize {
+        let blocks = self.memory_blocks as usize * BLOCK_SIZE;
+        let pulses = if blocks < MAX_PULSES {
+            blocks
+        } else {
+            MAX_PULSES
+        };
+        // One pulse per bit, and the reset at the end
+        (pulses - 1) / (self.order.get_channel_count() * 8)
+    }
+}
+
+/// WS2812 or SK6812 LEDs chained on one data pin.
+pub struct LedStrip {
+    channel: Channel<'static, Async, Tx>,
+    order: ColorOrder,
+    brightness: u8,
+    gamma: [u8; 256],
+    capacity: usize,
+}
+
+impl LedStrip {
+    pub fn new(
+        channel: impl TxChannelCreator<'static, Async>,
+        pin: AnyPin<'static>,
+        config: StripConfig,
+    ) -> Self {
+        let tx_config = TxChannelConfig::default()
+            .with_clk_divider(CLOCK_DIVIDER)
+            .with_memsize(config.memory_blocks);
+        let channel = channel.configure_tx(&tx_config).unwrap().with_pin(pin);
+
+        let mut gamma = [0; 256];
+        for (value, corrected) in gamma.iter_mut().enumerate() {
+            *corrected = (libm::powf(value as f32 / 255.0, config.gamma) * 255.0 + 0.5) as u8;
+        }
+
+        Self {
+            channel,
+            order: config.order,
+            brightness: config.brightness,
+            gamma,
+            capacity: config.get_capacity(),
+        }
+    }
+
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    /// Send the colors to the strip, pixels past its capacity are left out.
+    pub async fn write(&mut self, pixels: &[RGB8]) -> Result<(), rmt::Error> {
+        let mut pulses = [PulseCode::end_marker(); MAX_PULSES];
+        let mut count = 0;
+        let channel_count = self.order.get_channel_count();
+
+        for pixel in pixels.iter().take(self.capacity) {
+            let channels = self.order.get_channels(*pixel);
+
+            for value in &channels[..channel_count] {
+                let scaled = (*value as u16 * self.brightness as u16 / 255) as u8;
+                let corrected = self.gamma[scaled as usize];
+
+                for bit in (0..8).rev() {
+                    pulses[count] = if corrected & (1 << bit) != 0 {
+                        high_pulse()
+                    } else {
+                        low_pulse()
+                    };
+                    count += 1;
+                }
+            }
+        }
+
+        // Latches the colors, its zero length second half also ends the transmission
+        pulses[count] = reset_pulse();
+        count += 1;
+
+        self.channel.transmit(&pulses[..count]).await
+    }
+}
+
+fn reset_pulse() -> PulseCode {
+    PulseCode::new(
+        Level::Low,
+        duration_to_ticks(&Duration::from_micros(80)),
+        Level::Low,
+        0,
+    )
+}
+
+fn high_pulse() -> PulseCode {
+    PulseCode::new(
+        Level::High,
+        duration_to_ticks(&Duration::from_nanos(800)),
+        Level::Low,
+        duration_to_ticks(&Duration::from_nanos(450)),
+    )
+}
+
+fn low_pulse() -> PulseCode {
+    PulseCode::new(
+        Level::High,
+        duration_to_ticks(&Duration::from_nanos(400)),
+        Level::Low,
+        duration_to_ticks(&Duration::from_nanos(850)),
+    )
+}
+
+fn duration_to_ticks(duration: &Duration) -> u16 {
+    let ticks = duration.as_nanos() * TICKS_PER_HZ.as_hz() as u128 / 1_000_000_000;
+    u16::try_from(ticks).unwrap()
+}
@@ -0,0 +1,112 @@
+use core::cell::Cell;
+use core::ops::Range;
+
+use config::{CHEST_PIXELS, EYE_PIXELS, PIXEL_COUNT, STRIP_CONFIG};
+use defmt::{info, warn};
+use driver::LedStrip;
+use embassy_futures::select::{Either, select};
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    signal::Signal,
+};
+use esp_hal::{Async, gpio::AnyPin, rmt::ChannelCreator};
+use rgb::RGB8;
+
+use crate::modules::servo::config::SERVO_COUNT;
+
+pub mod config;
+pub mod driver;
+
+const TAG: &str = "[LEDS]";
+
+/// Brightness of the whole strip, 255 is full brightness.
+pub static LED_BRIGHTNESS: Signal<CriticalSectionRawMutex, u8> = Signal::new();
+
+static LEDS_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+static PIXELS: Mutex<CriticalSectionRawMutex, Cell<[RGB8; PIXEL_COUNT]>> =
+    Mutex::new(Cell::new([RGB8::new(0, 0, 0); PIXEL_COUNT]));
+
+pub const LED_GROUP_COUNT: usize = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LedGroup {
+    Eyes = 0,
+    Chest = 1,
+}
+
+impl LedGroup {
+    pub const ALL: [LedGroup; LED_GROUP_COUNT] = [LedGroup::Eyes, LedGroup::Chest];
+
+    /// Animation tracks of the LED groups come after the servos.
+    pub fn get_track(&self) -> usize {
+        SERVO_COUNT + *self as usize
+    }
+
+    pub fn get_from_track(track: usize) -> Option<LedGroup> {
+        LedGroup::ALL
+            .into_iter()
+            .find(|group| group.get_track() == track)
+    }
+
+    pub fn get_from_binary(payload: &[u8]) -> Option<LedGroup> {
+        match payload {
+            b"eyes" => Some(LedGroup::Eyes),
+            b"chest" => Some(LedGroup::Chest),
+            _ => None,
+        }
+    }
+
+    fn get_pixels(&self) -> Range<usize> {
+        match self {
+            LedGroup::Eyes => EYE_PIXELS,
+            LedGroup::Chest => CHEST_PIXELS,
+        }
+    }
+}
+
+/// Parse a color like `#ff8000`.
+pub fn parse_color(payload: &[u8]) -> Option<RGB8> {
+    let hex = core::str::from_utf8(payload).ok()?.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+    Some(RGB8::new(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Light every pixel of the group in `color`.
+pub fn set_group(group: LedGroup, color: RGB8) {
+    let changed = PIXELS.lock(|pixels| {
+        let mut colors = pixels.get();
+        let group_pixels = &mut colors[group.get_pixels()];
+        let changed = group_pixels.iter().any(|pixel| *pixel != color);
+        group_pixels.fill(color);
+        pixels.set(colors);
+        changed
+    });
+
+    // Animations set every group on every frame
+    if changed {
+        LEDS_CHANGED.signal(());
+    }
+}
+
+/// Eyes and chest.
+#[embassy_executor::task]
+pub async fn leds_task(channel: ChannelCreator<'static, Async, 0>, pin: AnyPin<'static>) {
+    let mut strip = LedStrip::new(channel, pin, STRIP_CONFIG);
+    info!("{} task started", TAG);
+
+    loop {
+        let pixels = PIXELS.lock(|pixels| pixels.get());
+        if let Err(error) = strip.write(&pixels).await {
+            warn!("{} Failed to write pixels: {:?}", TAG, error);
+        }
+
+        if let Either::Second(brightness) = select(LEDS_CHANGED.wait(), LED_BRIGHTNESS.wait()).await
+        {
+            strip.set_brightness(brightness);
+        }
+    }
+}
@@ -1,8 +1,17 @@
 pub mod audio;
 pub mod connectivity;
+pub mod crash;
+pub mod events;
 pub mod indicator;
 pub mod interaction;
+pub mod leds;
 pub mod mode;
 pub mod motion;
+pub mod power;
+pub mod quiet;
 pub mod servo;
+pub mod settings;
+pub mod status;
+pub mod touch;
 pub mod util;
+pub mod watchdog;
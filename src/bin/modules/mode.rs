@@ -1,22 +1,122 @@
-use defmt::info;
+use defmt::{info, warn};
 use embassy_executor::Spawner;
 use embassy_futures::select::select;
-use embassy_time::Timer;
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    watch::{Receiver, Watch},
+};
+use embassy_time::{Duration, Timer};
 use esp_hal::{
     gpio::{Event, Input, InputConfig, InputPin, Pull, WakeEvent},
     system::software_reset,
 };
 use rgb::RGB8;
 
-use crate::modules::indicator::INDICATOR_QUEUE;
+use crate::modules::{
+    indicator::{Priority, effects::Effect, set_effect},
+    power::report_activity,
+};
+
+const TAG: &str = "[MODE]";
+
+/// Time the mode color takes to fade in.
+const MODE_FADE: Duration = Duration::from_millis(500);
+/// Time the switch takes to settle after it moved.
+const SWITCH_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Wi-Fi, motion sensor and power.
+const MODE_RECEIVERS: usize = 3;
+
+/// The mode the owl is in, tasks pause and resume themselves when it changes.
+pub static SYSTEM_MODE: Watch<CriticalSectionRawMutex, SystemMode, MODE_RECEIVERS> =
+    Watch::new_with(SystemMode::Off);
+
+pub type ModeReceiver = Receiver<'static, CriticalSectionRawMutex, SystemMode, MODE_RECEIVERS>;
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum SystemMode {
     Play,
     Mailbox,
+    /// Plays and stays connected, only picked over MQTT.
+    Hybrid,
+    /// Servos parked, speaker and Wi-Fi off, until the switch moves again.
     Off,
 }
 
+impl SystemMode {
+    /// Indicator color while running in this mode.
+    pub fn get_color(&self) -> RGB8 {
+        match self {
+            SystemMode::Play => RGB8::new(255, 0, 0),
+            SystemMode::Mailbox => RGB8::new(0, 0, 255),
+            SystemMode::Hybrid => RGB8::new(160, 0, 255),
+            SystemMode::Off => RGB8::new(0, 0, 0),
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            SystemMode::Play => "play",
+            SystemMode::Mailbox => "mailbox",
+            SystemMode::Hybrid => "hybrid",
+            SystemMode::Off => "off",
+        }
+    }
+
+    /// Modes that can be picked over MQTT, the ones that stay connected so MQTT can change it back.
+    pub fn get_from_binary(payload: &[u8]) -> Option<SystemMode> {
+        match payload {
+            b"mailbox" => Some(SystemMode::Mailbox),
+            b"hybrid" => Some(SystemMode::Hybrid),
+            _ => None,
+        }
+    }
+
+    /// Reacts to being handled and follows the body with the head.
+    pub fn is_interactive(&self) -> bool {
+        matches!(self, SystemMode::Play | SystemMode::Hybrid)
+    }
+
+    /// Connected to Wi-Fi and MQTT, so it never sleeps.
+    pub fn is_networked(&self) -> bool {
+        matches!(self, SystemMode::Mailbox | SystemMode::Hybrid)
+    }
+}
+
+pub fn get_mode() -> SystemMode {
+    SYSTEM_MODE.try_get().unwrap_or(SystemMode::Off)
+}
+
+pub fn set_mode(mode: SystemMode) {
+    let previous = get_mode();
+    if mode == previous {
+        return;
+    }
+
+    info!("{} {} -> {}", TAG, previous.get_name(), mode.get_name());
+
+    let fade = Effect::Fade {
+        from: previous.get_color(),
+        to: mode.get_color(),
+        duration: MODE_FADE,
+    };
+    set_effect(Priority::Base, fade);
+
+    // Don't fall asleep right after leaving a mode that stays awake
+    report_activity();
+    SYSTEM_MODE.sender().send(mode);
+}
+
+/// Position of the switch, `None` while it is between two positions.
+fn read_switch(play_state: &Input, common_state: &Input) -> Option<SystemMode> {
+    match (play_state.is_low(), common_state.is_low()) {
+        (true, true) => Some(SystemMode::Play),
+        (false, true) => Some(SystemMode::Mailbox),
+        (false, false) => Some(SystemMode::Off),
+        (true, false) => None,
+    }
+}
+
 pub async fn initialize_mode(
     spawner: Spawner,
     mode_pin_1: impl InputPin + 'static,
@@ -26,78 +126,57 @@ pub async fn initialize_mode(
     let play_state = Input::new(mode_pin_1, input_button_cfg);
     let mut common_state = Input::new(mode_pin_2, input_button_cfg);
 
-    let system_mode = match (play_state.is_low(), common_state.is_low()) {
-        (true, true) => SystemMode::Play,
-        (false, true) => SystemMode::Mailbox,
-        (false, false) => SystemMode::Off,
-        _ => software_reset(),
-    };
-
-    match system_mode {
-        SystemMode::Play => {
-            info!("System mode: Play");
-            INDICATOR_QUEUE.signal(RGB8::new(255, 0, 0));
-        }
-        SystemMode::Mailbox => {
-            info!("System mode: Mailbox");
-            INDICATOR_QUEUE.signal(RGB8::new(0, 0, 255));
-        }
-        SystemMode::Off => {
-            info!("off");
-            INDICATOR_QUEUE.signal(RGB8::new(0, 0, 0));
-            Timer::after(embassy_time::Duration::from_millis(100)).await;
+    let mut system_mode =
+        read_switch(&play_state, &common_state).unwrap_or_else(|| software_reset());
 
-            common_state.listen(Event::LowLevel);
-            common_state
-                .wakeup_enable(true, WakeEvent::LowLevel)
-                .unwrap();
-            common_state.wait_for_low().await;
+    while system_mode == SystemMode::Off {
+        info!("{} off", TAG);
 
-            info!("turning on");
+        common_state.listen(Event::LowLevel);
+        common_state
+            .wakeup_enable(true, WakeEvent::LowLevel)
+            .unwrap();
+        common_state.wait_for_low().await;
+        common_state
+            .wakeup_enable(false, WakeEvent::LowLevel)
+            .unwrap();
 
-            // restart
-            software_reset();
-        }
+        Timer::after(SWITCH_DEBOUNCE).await;
+        system_mode = read_switch(&play_state, &common_state).unwrap_or(SystemMode::Off);
     }
 
+    info!("{} System mode: {}", TAG, system_mode.get_name());
+    set_mode(system_mode);
+
     spawner.spawn(mode_task(play_state, common_state, system_mode).unwrap());
 
     system_mode
 }
 
+/// Follows the mode switch.
 #[embassy_executor::task]
 pub async fn mode_task(
     mut play_state: Input<'static>,
     mut common_state: Input<'static>,
-    mode: SystemMode,
+    mut switch_mode: SystemMode,
 ) {
     loop {
-        match mode {
-            SystemMode::Play => {
-                play_state.wait_for_rising_edge().await;
-                Timer::after(embassy_time::Duration::from_millis(10)).await;
-                if play_state.is_low() {
-                    info!("exit play debounced");
-                    continue;
-                }
-                info!("exited play mode");
-                software_reset();
-            }
-            SystemMode::Mailbox => {
-                select(
-                    play_state.wait_for_falling_edge(),
-                    common_state.wait_for_rising_edge(),
-                )
-                .await;
-                Timer::after(embassy_time::Duration::from_millis(10)).await;
-                if common_state.is_low() && play_state.is_high() {
-                    info!("Exit mailbox debounced");
-                    continue;
-                }
-                info!("exited mailbox mode");
-                software_reset();
-            }
-            SystemMode::Off => {}
+        select(
+            play_state.wait_for_any_edge(),
+            common_state.wait_for_any_edge(),
+        )
+        .await;
+        Timer::after(SWITCH_DEBOUNCE).await;
+
+        let Some(mode) = read_switch(&play_state, &common_state) else {
+            warn!("{} Switch between two positions", TAG);
+            continue;
+        };
+
+        // Only follow the switch when it moved, MQTT may have picked another mode since
+        if mode != switch_mode {
+            switch_mode = mode;
+            set_mode(mode);
         }
     }
 }
@@ -0,0 +1,27 @@
+use embassy_time::Duration;
+
+/// Output data rate of the sensor.
+pub const SAMPLE_RATE_HZ: u32 = 200;
+pub const SAMPLE_PERIOD_MS: u64 = 1_000 / SAMPLE_RATE_HZ as u64;
+/// Samples read from the FIFO at once, it holds at most 73.
+pub const FIFO_BATCH: usize = 32;
+/// Time between two reads of the FIFO, half a batch is waiting by then.
+pub const FIFO_DRAIN_PERIOD: Duration =
+    Duration::from_millis(FIFO_BATCH as u64 / 2 * SAMPLE_PERIOD_MS);
+/// Consecutive failed reads before the sensor is set up again.
+pub const MAX_READ_FAILURES: u32 = 5;
+pub const READ_RETRY_DELAY: Duration = Duration::from_millis(10);
+/// Failed setups before giving up on the sensor for a while.
+pub const MAX_SETUP_ATTEMPTS: u32 = 5;
+/// Grows with every failed setup.
+pub const SETUP_RETRY_DELAY: Duration = Duration::from_secs(1);
+pub const FAILED_RETRY_DELAY: Duration = Duration::from_secs(60);
+/// Time the sensor is kept off to reset it.
+pub const SENSOR_POWER_OFF: Duration = Duration::from_millis(100);
+
+/// Movement that wakes the owl from sleep, in units of 2 mg.
+pub const WAKE_MOTION_THRESHOLD: u8 = 40;
+
+/// Neck servo units per degree of yaw, negative turns the head against the body.
+pub const NECK_COMPENSATION: f32 = -5.5;
+pub const HEAD_TRACKING_FADE: Duration = Duration::from_secs(1);
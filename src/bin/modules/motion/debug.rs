@@ -0,0 +1,63 @@
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel, signal::Signal,
+};
+
+use super::config::SAMPLE_RATE_HZ;
+use super::gesture::{Gesture, ImuSample};
+use super::orientation::Attitude;
+
+/// Samples per second to stream for tuning, 0 turns streaming off.
+pub static DEBUG_RATE: Signal<CriticalSectionRawMutex, u32> = Signal::new();
+
+/// Samples waiting to be sent, new ones are dropped when the network can't keep up.
+pub static DEBUG_SAMPLES: Channel<CriticalSectionRawMutex, DebugSample, 64> = Channel::new();
+
+#[derive(Clone, Copy)]
+pub struct DebugSample {
+    pub sample: ImuSample,
+    pub attitude: Attitude,
+    pub gesture: Option<Gesture>,
+}
+
+/// Picks every nth sample to stream.
+pub struct DebugStream {
+    interval: u32,
+    counter: u32,
+}
+
+impl DebugStream {
+    pub const fn new() -> Self {
+        Self {
+            interval: 0,
+            counter: 0,
+        }
+    }
+
+    pub fn push(&mut self, sample: &ImuSample, attitude: &Attitude, gesture: Option<Gesture>) {
+        if let Some(rate) = DEBUG_RATE.try_take() {
+            self.interval = match rate {
+                0 => 0,
+                rate => (SAMPLE_RATE_HZ / rate.min(SAMPLE_RATE_HZ)).max(1),
+            };
+            self.counter = 0;
+        }
+
+        if self.interval == 0 {
+            return;
+        }
+
+        // Never skip a sample that completed a gesture
+        self.counter += 1;
+        if self.counter < self.interval && gesture.is_none() {
+            return;
+        }
+        self.counter = 0;
+
+        let debug_sample = DebugSample {
+            sample: *sample,
+            attitude: *attitude,
+            gesture,
+        };
+        DEBUG_SAMPLES.try_send(debug_sample).ok();
+    }
+}
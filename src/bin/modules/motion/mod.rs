@@ -1,104 +1,263 @@
-use defmt::info;
-use embassy_time::{Duration, Instant, Timer};
-use esp_hal::gpio::{AnyPin, Level, Output, OutputConfig};
+use defmt::{info, warn};
+use embassy_futures::select::{Either3, select, select3};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::{Instant, Ticker, Timer};
+use esp_hal::gpio::{AnyPin, Input, InputConfig, Level, Output, OutputConfig, Pull, WakeEvent};
 use esp_hal::i2c::master::{Config, I2c};
 use esp_hal::peripherals::I2C0;
-use hayasen::mpu6050::{AccelRange, GyroRange};
-use hayasen::mpu6050_hayasen;
-use num_traits::float::FloatCore;
+use esp_hal::time::Rate;
 
-use crate::modules::servo::animation::ANIMATION_QUEUE;
-use crate::modules::servo::animations::AnimationType;
+use crate::modules::events::{Event, publish_event};
+use crate::modules::mode::{ModeReceiver, SYSTEM_MODE, get_mode};
+use crate::modules::power::{POWER_STATE, PowerReceiver, PowerState, report_activity};
+use crate::modules::servo::layers::{self, LayerId};
+use crate::modules::settings::{get_settings, update_settings};
+use crate::modules::status::{Component, Health, report_health};
+
+use calibration::{Calibrator, ImuCalibration};
+use config::{
+    FAILED_RETRY_DELAY, FIFO_BATCH, FIFO_DRAIN_PERIOD, HEAD_TRACKING_FADE, MAX_READ_FAILURES,
+    MAX_SETUP_ATTEMPTS, NECK_COMPENSATION, READ_RETRY_DELAY, SAMPLE_PERIOD_MS, SAMPLE_RATE_HZ,
+    SENSOR_POWER_OFF, SETUP_RETRY_DELAY, WAKE_MOTION_THRESHOLD,
+};
+use debug::DebugStream;
+use gesture::{Gesture, GestureRecognizer, ImuSample};
+use mpu6050::{Error, Mpu6050, recover_bus};
+use orientation::{ComplementaryFilter, set_attitude};
+
+pub mod config;
+pub mod debug;
+pub mod mpu6050;
+pub mod orientation;
+
+pub use owlimatronic_logic::motion::{calibration, gesture, trace};
 
 const TAG: &str = "[MOTION]";
 
+/// Sample the resting sensor and store its offsets.
+pub static CALIBRATE_TRIGGER: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Everything that happens to a sample after it is read.
+struct MotionProcessor {
+    calibration: ImuCalibration,
+    calibrator: Option<Calibrator>,
+    recognizer: GestureRecognizer,
+    filter: ComplementaryFilter,
+    debug: DebugStream,
+}
+
+impl MotionProcessor {
+    fn new() -> Self {
+        Self {
+            calibration: get_settings()
+                .imu_calibration
+                .unwrap_or(ImuCalibration::NONE),
+            calibrator: None,
+            recognizer: GestureRecognizer::new(),
+            filter: ComplementaryFilter::new(),
+            debug: DebugStream::new(),
+        }
+    }
+
+    fn process(&mut self, mut sample: ImuSample) -> Option<Gesture> {
+        if CALIBRATE_TRIGGER.try_take().is_some() {
+            info!("{} Calibrating, keep still...", TAG);
+            self.calibrator = Some(Calibrator::new());
+        }
+
+        // Calibration works on the raw readings
+        if let Some(calibrator) = self.calibrator.as_mut() {
+            calibrator.add(&sample);
+            if !calibrator.is_done() {
+                return None;
+            }
+
+            match calibrator.finish() {
+                Some(calibration) => {
+                    info!("{} Calibrated", TAG);
+                    self.calibration = calibration;
+                    update_settings(|settings| settings.imu_calibration = Some(calibration));
+                    self.filter = ComplementaryFilter::new();
+                    self.recognizer = GestureRecognizer::new();
+                }
+                None => warn!("{} Moved while calibrating, keeping old calibration", TAG),
+            }
+            self.calibrator = None;
+            return None;
+        }
+
+        self.calibration.apply(&mut sample);
+
+        // Keep the head looking the same way while the body is turned
+        let attitude = self.filter.update(&sample);
+        set_attitude(attitude);
+        let neck_offset = libm::roundf(attitude.yaw * NECK_COMPENSATION);
+        layers::set_value(LayerId::HeadTracking, neck_offset as i16);
+
+        let gesture = self.recognizer.update(&sample);
+        self.debug.push(&sample, &attitude, gesture);
+        gesture
+    }
+}
+
 #[embassy_executor::task]
 pub async fn motion_task(
-    i2c: I2C0<'static>,
+    mut i2c: I2C0<'static>,
     power_pin: AnyPin<'static>,
-    clock_pin: AnyPin<'static>,
-    data_pin: AnyPin<'static>,
+    mut clock_pin: AnyPin<'static>,
+    mut data_pin: AnyPin<'static>,
+    interrupt_pin: AnyPin<'static>,
 ) {
     info!("{} Starting task...", TAG);
-    let mut sensor_power = Output::new(power_pin, Level::High, OutputConfig::default());
-    sensor_power.set_high();
-    Timer::after_millis(300).await;
-
-    let i2c = I2c::new(i2c, Config::default())
-        .unwrap()
-        .with_sda(data_pin)
-        .with_scl(clock_pin);
-
-    let mut sensor = match mpu6050_hayasen::create_default(i2c, 0x68) {
-        Ok(s) => s,
-        Err(e) => {
-            match e {
-                hayasen::Error::ConfigError => info!("{} Configuration error", TAG),
-                hayasen::Error::I2c(_) => info!("{} I2C communication error", TAG),
-                hayasen::Error::InvalidData => info!("{} Invalid data received", TAG),
-                hayasen::Error::NotDetected => info!("{} Sensor not detected", TAG),
-                hayasen::Error::SensorSpecific(msg) => {
-                    info!("{} Sensor-specific error: {}", TAG, msg)
+    let mut sensor_power = Output::new(power_pin, Level::Low, OutputConfig::default());
+    let mut interrupt = Input::new(interrupt_pin, InputConfig::default().with_pull(Pull::Down));
+    let mut power_state = POWER_STATE.receiver().unwrap();
+    let mut mode = SYSTEM_MODE.receiver().unwrap();
+
+    let mut processor = MotionProcessor::new();
+    let mut failed_attempts = 0;
+
+    loop {
+        // Power cycling resets a sensor that stopped responding
+        sensor_power.set_low();
+        Timer::after(SENSOR_POWER_OFF).await;
+        sensor_power.set_high();
+        Timer::after_millis(300).await;
+
+        recover_bus(clock_pin.reborrow(), data_pin.reborrow()).await;
+
+        let config = Config::default().with_frequency(Rate::from_khz(400));
+        let bus = I2c::new(i2c.reborrow(), config)
+            .unwrap()
+            .with_sda(data_pin.reborrow())
+            .with_scl(clock_pin.reborrow())
+            .into_async();
+
+        let mut sensor = match Mpu6050::new(bus, SAMPLE_RATE_HZ).await {
+            Ok(sensor) => sensor,
+            Err(error) => {
+                failed_attempts += 1;
+                warn!(
+                    "{} Failed to set up sensor ({}/{}): {}",
+                    TAG, failed_attempts, MAX_SETUP_ATTEMPTS, error
+                );
+
+                // Animations keep working without motion, try again once in a while
+                if failed_attempts >= MAX_SETUP_ATTEMPTS {
+                    report_health(Component::Motion, Health::Failed);
+                    Timer::after(FAILED_RETRY_DELAY).await;
+                    failed_attempts = 0;
+                } else {
+                    report_health(Component::Motion, Health::Degraded);
+                    Timer::after(SETUP_RETRY_DELAY * failed_attempts).await;
                 }
+                continue;
             }
-            return;
-        }
-    };
+        };
 
-    sensor.setup_accelerometer(AccelRange::Range2G).unwrap();
-    sensor.setup_gyroscope(GyroRange::Range250Dps).unwrap();
-    sensor.set_sample_rate(16).unwrap();
-    sensor.wake_up().unwrap();
+        info!("{} Sensor ready", TAG);
+        failed_attempts = 0;
+        report_health(Component::Motion, Health::Ok);
+        set_head_tracking(get_mode().is_interactive());
+
+        let error = run_sensor(
+            &mut sensor,
+            &mut interrupt,
+            &mut power_state,
+            &mut mode,
+            &mut processor,
+        )
+        .await;
+
+        warn!("{} Sensor stopped responding: {}", TAG, error);
+        report_health(Component::Motion, Health::Degraded);
+        set_head_tracking(false);
+    }
+}
+
+/// Only follow the body with the head in modes that play.
+fn set_head_tracking(enabled: bool) {
+    let weight = if enabled { 1.0 } else { 0.0 };
+    layers::set_weight(LayerId::HeadTracking, weight, HEAD_TRACKING_FADE);
+}
 
-    const SAMPLE_SIZE: usize = 10;
-    let mut average_accel: [f32; SAMPLE_SIZE] = [0.0; SAMPLE_SIZE];
-    let mut last_trigger = Instant::now();
+/// Read and process samples until the sensor keeps failing.
+async fn run_sensor(
+    sensor: &mut Mpu6050<'_>,
+    interrupt: &mut Input<'_>,
+    power_state: &mut PowerReceiver,
+    mode: &mut ModeReceiver,
+    processor: &mut MotionProcessor,
+) -> Error {
+    let mut batch = [ImuSample::default(); FIFO_BATCH];
+    let mut last_timestamp_ms = 0;
+    let mut read_failures = 0;
+    let mut ticker = Ticker::every(FIFO_DRAIN_PERIOD);
 
     loop {
-        Timer::after_millis(50).await;
-
-        let (_temp, accel, _gyro) = mpu6050_hayasen::read_all(&mut sensor).unwrap();
-        // accel[0]; // up down
-        // accel[1]; // left right
-        // accel[2]; // forward back
-
-        //  Calculate total acceleration magnitude (subtract gravity)
-        let total_accel = libm::sqrtf(accel[0].powi(2) + accel[1].powi(2) + accel[2].powi(2));
-        let motion_accel = (total_accel - 1.0).abs(); // Subtract 1g gravity
-        let avg_motion_accel: f32 = average_accel.iter().sum::<f32>() / SAMPLE_SIZE as f32;
-
-        // Calculate total angular velocity
-        // let total_gyro = libm::sqrtf(gyro[0].powi(2) + gyro[1].powi(2) + gyro[2].powi(2));
-
-        // If didn't recently play animation
-        if (last_trigger + Duration::from_secs(2)) < Instant::now() {
-            // Picked up
-            if (accel[0] - 0.96).abs() > 0.3 && avg_motion_accel < 0.1 {
-                info!("{} Picked up!", TAG);
-                ANIMATION_QUEUE.send(AnimationType::PickedUp).await;
-                last_trigger = Instant::now();
+        // Drain the FIFO on time, or right away when it overflowed
+        let data = select(ticker.next(), interrupt.wait_for_high());
+        let changed = select3(data, power_state.changed(), mode.changed()).await;
+        if let Either3::Third(mode) = changed {
+            set_head_tracking(mode.is_interactive());
+            continue;
+        }
+
+        if let Either3::Second(PowerState::Sleeping) = changed {
+            // Being moved wakes the owl
+            if let Err(error) = sensor.enable_motion_interrupt(WAKE_MOTION_THRESHOLD).await {
+                warn!("{} Failed to enable motion wake: {}", TAG, error);
+            }
+            interrupt.wakeup_enable(true, WakeEvent::HighLevel).ok();
+
+            power_state
+                .changed_and(|state| *state == PowerState::Awake)
+                .await;
+
+            interrupt.wakeup_enable(false, WakeEvent::HighLevel).ok();
+            if let Err(error) = sensor.enable_fifo_interrupt().await {
+                return error;
+            }
+            *processor = MotionProcessor::new();
+            continue;
+        }
+
+        let count = match sensor.read_fifo(&mut batch).await {
+            Ok(count) => {
+                read_failures = 0;
+                count
+            }
+            // Samples were lost, but the sensor is fine
+            Err(Error::FifoOverflow) => {
+                warn!("{} FIFO overflow", TAG);
+                continue;
             }
+            Err(error) => {
+                read_failures += 1;
+                if read_failures >= MAX_READ_FAILURES {
+                    return error;
+                }
+                warn!("{} Failed to read samples: {}", TAG, error);
+                Timer::after(READ_RETRY_DELAY).await;
+                continue;
+            }
+        };
+
+        // The samples were taken at a fixed rate, the last one just now
+        let now = Instant::now().as_millis();
+        for (index, sample) in batch[..count].iter_mut().enumerate() {
+            let age = (count - 1 - index) as u64 * SAMPLE_PERIOD_MS;
+            sample.timestamp_ms = now.saturating_sub(age).max(last_timestamp_ms + 1);
+            last_timestamp_ms = sample.timestamp_ms;
         }
 
-        // Update moving average
-        for i in (1..SAMPLE_SIZE).rev() {
-            average_accel[i] = average_accel[i - 1];
+        for sample in batch[..count].iter() {
+            let Some(gesture) = processor.process(*sample) else {
+                continue;
+            };
+            info!("{} Gesture: {}", TAG, gesture.get_name());
+            report_activity();
+            publish_event(Event::Gesture(gesture));
         }
-        average_accel[0] = motion_accel;
-
-        // // Detect motion
-        // if motion_accel > ACCEL_THRESHOLD || total_gyro > GYRO_THRESHOLD {
-        //     info!(
-        //         "{} Motion detected! Accel: {}g, Gyro: {}°/s",
-        //         TAG, motion_accel, total_gyro
-        //     );
-
-        //     if embassy_time::Instant::now() - last_trigger_time
-        //         > embassy_time::Duration::from_secs(5)
-        //     {
-        //         ANIMATION_QUEUE.send(AnimationType::Flap).await;
-        //         last_trigger_time = embassy_time::Instant::now();
-        //     }
-        // }
     }
 }
@@ -0,0 +1,187 @@
+use embassy_time::Timer;
+use esp_hal::{
+    Async,
+    gpio::{AnyPin, DriveMode, Input, InputConfig, Level, Output, OutputConfig, Pull},
+    i2c::master::{self, I2c},
+};
+
+use super::gesture::ImuSample;
+
+pub const ADDRESS: u8 = 0x68;
+
+const SMPLRT_DIV: u8 = 0x19;
+const CONFIG: u8 = 0x1A;
+const GYRO_CONFIG: u8 = 0x1B;
+const ACCEL_CONFIG: u8 = 0x1C;
+const MOT_THR: u8 = 0x1F;
+const MOT_DUR: u8 = 0x20;
+const FIFO_EN: u8 = 0x23;
+const INT_PIN_CFG: u8 = 0x37;
+const INT_ENABLE: u8 = 0x38;
+const INT_STATUS: u8 = 0x3A;
+const USER_CTRL: u8 = 0x6A;
+const PWR_MGMT_1: u8 = 0x6B;
+const FIFO_COUNT_H: u8 = 0x72;
+const FIFO_R_W: u8 = 0x74;
+const WHO_AM_I: u8 = 0x75;
+
+/// The gyro runs at 1 kHz with the low pass filter enabled.
+const GYRO_OUTPUT_RATE: u32 = 1_000;
+/// Accelerometer, temperature and gyro, in the order of the data registers.
+const FIFO_SAMPLE_SIZE: usize = 14;
+const FIFO_SIZE: usize = 1_024;
+
+// Sensitivity at ±2g and ±250°/s
+const ACCEL_SCALE: f32 = 16_384.0;
+const GYRO_SCALE: f32 = 131.0;
+
+#[derive(Debug, defmt::Format)]
+pub enum Error {
+    I2c(master::Error),
+    NotDetected,
+    /// Samples were lost, the FIFO has been reset.
+    FifoOverflow,
+}
+
+impl From<master::Error> for Error {
+    fn from(error: master::Error) -> Self {
+        Error::I2c(error)
+    }
+}
+
+/// MPU6050 streaming samples through its FIFO, signalling overflows and motion on the INT pin.
+pub struct Mpu6050<'d> {
+    i2c: I2c<'d, Async>,
+}
+
+impl<'d> Mpu6050<'d> {
+    /// Reset the sensor and start sampling at `sample_rate` Hz.
+    pub async fn new(i2c: I2c<'d, Async>, sample_rate: u32) -> Result<Self, Error> {
+        let mut sensor = Self { i2c };
+
+        if sensor.read_register(WHO_AM_I).await? != ADDRESS {
+            return Err(Error::NotDetected);
+        }
+
+        sensor.write_register(PWR_MGMT_1, 0x80).await?;
+        Timer::after_millis(100).await;
+
+        // Clock from the X gyro, more stable than the internal oscillator
+        sensor.write_register(PWR_MGMT_1, 0x01).await?;
+        // Low pass filter at 44 Hz
+        sensor.write_register(CONFIG, 0x03).await?;
+        let divider = (GYRO_OUTPUT_RATE / sample_rate.clamp(4, GYRO_OUTPUT_RATE)) - 1;
+        sensor.write_register(SMPLRT_DIV, divider as u8).await?;
+        sensor.write_register(GYRO_CONFIG, 0x00).await?;
+        sensor.write_register(ACCEL_CONFIG, 0x00).await?;
+
+        // Latch INT high until any register is read
+        sensor.write_register(INT_PIN_CFG, 0x30).await?;
+        // Temperature, gyro and accelerometer into the FIFO
+        sensor.write_register(FIFO_EN, 0xF8).await?;
+        sensor.enable_fifo_interrupt().await?;
+
+        Ok(sensor)
+    }
+
+    /// Only raise INT when the FIFO overflows, the samples are read on a timer.
+    pub async fn enable_fifo_interrupt(&mut self) -> Result<(), Error> {
+        self.write_register(ACCEL_CONFIG, 0x00).await?;
+        self.write_register(INT_ENABLE, 0x10).await?;
+        // Drop whatever was sampled in the meantime
+        self.reset_fifo().await?;
+        self.read_register(INT_STATUS).await?;
+        Ok(())
+    }
+
+    /// Only raise INT when the sensor is moved, in units of 2 mg.
+    pub async fn enable_motion_interrupt(&mut self, threshold: u8) -> Result<(), Error> {
+        self.write_register(MOT_THR, threshold).await?;
+        self.write_register(MOT_DUR, 1).await?;
+        // Motion detection compares against the high pass filtered accelerometer
+        self.write_register(ACCEL_CONFIG, 0x01).await?;
+        self.write_register(INT_ENABLE, 0x40).await?;
+        self.read_register(INT_STATUS).await?;
+        Ok(())
+    }
+
+    /// Read every sample waiting in the FIFO into `samples`, oldest first.
+    ///
+    /// Timestamps are left at 0, the caller knows the sample rate.
+    pub async fn read_fifo(&mut self, samples: &mut [ImuSample]) -> Result<usize, Error> {
+        // Also clears the latched interrupt
+        let status = self.read_register(INT_STATUS).await?;
+        if status & 0x10 != 0 {
+            self.reset_fifo().await?;
+            return Err(Error::FifoOverflow);
+        }
+
+        let mut count = [0u8; 2];
+        self.i2c
+            .write_read_async(ADDRESS, &[FIFO_COUNT_H], &mut count)
+            .await?;
+        let available = (u16::from_be_bytes(count) as usize).min(FIFO_SIZE) / FIFO_SAMPLE_SIZE;
+
+        let mut read = 0;
+        for sample in samples.iter_mut().take(available) {
+            let mut data = [0u8; FIFO_SAMPLE_SIZE];
+            self.i2c
+                .write_read_async(ADDRESS, &[FIFO_R_W], &mut data)
+                .await?;
+            *sample = parse_sample(&data);
+            read += 1;
+        }
+
+        Ok(read)
+    }
+
+    async fn reset_fifo(&mut self) -> Result<(), Error> {
+        self.write_register(USER_CTRL, 0x04).await?;
+        self.write_register(USER_CTRL, 0x40).await
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error> {
+        let mut value = [0u8];
+        self.i2c
+            .write_read_async(ADDRESS, &[register], &mut value)
+            .await?;
+        Ok(value[0])
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error> {
+        self.i2c.write_async(ADDRESS, &[register, value]).await?;
+        Ok(())
+    }
+}
+
+/// Free the bus when a transfer was cut off while the sensor was holding SDA low.
+///
+/// Clocks SCL until the sensor has shifted out the rest of its byte and lets go.
+pub async fn recover_bus(clock_pin: AnyPin<'_>, data_pin: AnyPin<'_>) {
+    let data = Input::new(data_pin, InputConfig::default().with_pull(Pull::Up));
+    let config = OutputConfig::default()
+        .with_drive_mode(DriveMode::OpenDrain)
+        .with_pull(Pull::Up);
+    let mut clock = Output::new(clock_pin, Level::High, config);
+
+    for _ in 0..9 {
+        if data.is_high() {
+            break;
+        }
+        clock.set_low();
+        Timer::after_micros(5).await;
+        clock.set_high();
+        Timer::after_micros(5).await;
+    }
+}
+
+fn parse_sample(data: &[u8; FIFO_SAMPLE_SIZE]) -> ImuSample {
+    let value = |index: usize| i16::from_be_bytes([data[index * 2], data[index * 2 + 1]]) as f32;
+
+    ImuSample {
+        timestamp_ms: 0,
+        accel: [value(0), value(1), value(2)].map(|raw| raw / ACCEL_SCALE),
+        gyro: [value(4), value(5), value(6)].map(|raw| raw / GYRO_SCALE),
+        temperature: value(3) / 340.0 + 36.53,
+    }
+}
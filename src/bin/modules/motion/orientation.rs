@@ -0,0 +1,17 @@
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+
+pub use owlimatronic_logic::motion::orientation::{Attitude, ComplementaryFilter};
+
+static ATTITUDE: Mutex<CriticalSectionRawMutex, Cell<Attitude>> =
+    Mutex::new(Cell::new(Attitude::LEVEL));
+
+/// Latest attitude estimated by the motion task.
+pub fn get_attitude() -> Attitude {
+    ATTITUDE.lock(|attitude| attitude.get())
+}
+
+pub fn set_attitude(attitude: Attitude) {
+    ATTITUDE.lock(|cell| cell.set(attitude));
+}
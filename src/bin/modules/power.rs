@@ -0,0 +1,147 @@
+use core::cell::Cell;
+
+use defmt::info;
+use embassy_futures::select::select;
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    signal::Signal,
+    watch::{Receiver, Watch},
+};
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::rtc_cntl::{Rtc, sleep::GpioWakeupSource};
+
+use crate::modules::{
+    connectivity::clock,
+    mode::{SYSTEM_MODE, SystemMode},
+    servo::layers,
+    watchdog::is_all_idle,
+};
+
+const TAG: &str = "[POWER]";
+
+/// Sleep after this long without anyone playing with the owl.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Time the other tasks get to park the servos, stop the radio and set up their wake sources.
+const SLEEP_SETTLE: Duration = Duration::from_secs(1);
+/// Time between two checks for busy tasks before sleeping.
+const SLEEP_POLL: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PowerState {
+    Awake,
+    /// Idle in a mode that uses the network, only the radio saves power so MQTT keeps working.
+    Dozing,
+    /// Tasks that own a wake source enable it on their pin until the state changes back.
+    Sleeping,
+}
+
+/// Beak button, motion sensor, touch pads and Wi-Fi.
+const POWER_RECEIVERS: usize = 4;
+
+pub static POWER_STATE: Watch<CriticalSectionRawMutex, PowerState, POWER_RECEIVERS> =
+    Watch::new_with(PowerState::Awake);
+
+pub type PowerReceiver = Receiver<'static, CriticalSectionRawMutex, PowerState, POWER_RECEIVERS>;
+
+/// Servo and audio.
+const PAUSE_RECEIVERS: usize = 2;
+
+/// While set the servos are parked and the speaker is silent, until it is cleared again.
+pub static PAUSED: Watch<CriticalSectionRawMutex, bool, PAUSE_RECEIVERS> = Watch::new_with(false);
+
+static LAST_ACTIVITY: Mutex<CriticalSectionRawMutex, Cell<Instant>> =
+    Mutex::new(Cell::new(Instant::from_ticks(0)));
+
+/// Raised on every activity, wakes the owl up from dozing.
+static ACTIVITY: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Keep the owl awake for another [`IDLE_TIMEOUT`].
+pub fn report_activity() {
+    LAST_ACTIVITY.lock(|last| last.set(Instant::now()));
+    ACTIVITY.signal(());
+}
+
+/// Stop or resume everything that moves the servos or plays on the speaker.
+fn set_paused(paused: bool) {
+    layers::set_suspended(paused);
+    PAUSED.sender().send(paused);
+}
+
+fn idle_until() -> Instant {
+    LAST_ACTIVITY.lock(|last| last.get()) + IDLE_TIMEOUT
+}
+
+#[embassy_executor::task]
+pub async fn power_task(mut rtc: Rtc<'static>) {
+    info!("{} task started", TAG);
+    let sender = POWER_STATE.sender();
+    let mut mode = SYSTEM_MODE.receiver().unwrap();
+    report_activity();
+
+    loop {
+        let current = mode.get().await;
+
+        // Nothing moves while off, Wi-Fi disconnects on its own
+        if current == SystemMode::Off {
+            info!("{} Off, pausing", TAG);
+            set_paused(true);
+            mode.get_and(|mode| *mode != SystemMode::Off).await;
+
+            info!("{} Resuming", TAG);
+            set_paused(false);
+            report_activity();
+            continue;
+        }
+
+        // Activity pushes the deadline further while waiting
+        let deadline = idle_until();
+        if Instant::now() < deadline {
+            select(Timer::at(deadline), mode.changed()).await;
+            continue;
+        }
+
+        // Messages still have to come in, so only the breathing stops and the radio naps
+        if current.is_networked() {
+            info!("{} Idle, dozing", TAG);
+            layers::set_suspended(true);
+            sender.send(PowerState::Dozing);
+
+            ACTIVITY.reset();
+            select(ACTIVITY.wait(), mode.changed()).await;
+
+            info!("{} Stopped dozing", TAG);
+            sender.send(PowerState::Awake);
+            layers::set_suspended(false);
+            continue;
+        }
+
+        info!("{} Idle, going to sleep", TAG);
+
+        // Parks the servos, stops the speaker and the amplifier
+        set_paused(true);
+        sender.send(PowerState::Sleeping);
+        Timer::after(SLEEP_SETTLE).await;
+
+        // Sleeping blocks the executor, so every task has to finish what it is doing first
+        while !is_all_idle() && Instant::now() >= idle_until() {
+            Timer::after(SLEEP_POLL).await;
+        }
+
+        if Instant::now() >= idle_until() {
+            // Woken by the pins the other tasks enabled
+            let asleep_since = Instant::now();
+            let rtc_asleep_since = rtc.time_since_power_up();
+            rtc.sleep_light(&[&GpioWakeupSource::new()]);
+
+            // Only the RTC kept counting, the wall clock has to catch up
+            let slept = (rtc.time_since_power_up() - rtc_asleep_since).as_micros();
+            let counted = asleep_since.elapsed().as_micros();
+            clock::skip(Duration::from_micros(slept.saturating_sub(counted)));
+            info!("{} Woke up", TAG);
+        }
+
+        sender.send(PowerState::Awake);
+        set_paused(false);
+        report_activity();
+    }
+}
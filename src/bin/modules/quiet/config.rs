@@ -0,0 +1,16 @@
+use embassy_time::Duration;
+
+/// Volume of everything played while quiet, in percent on top of the other volumes.
+pub const QUIET_VOLUME: u8 = 30;
+
+/// Fraction of the distance from their default position the servos may move while quiet.
+pub const QUIET_AMPLITUDE: f32 = 0.5;
+/// Largest step of a servo in one frame while quiet, in servo units.
+pub const QUIET_MAX_STEP: u16 = 12;
+
+/// Quiet hours in local time, in minutes since midnight. They may wrap past midnight.
+pub const QUIET_START: u16 = 22 * 60;
+pub const QUIET_END: u16 = 7 * 60;
+
+/// Time between two checks of the schedule.
+pub const SCHEDULE_PERIOD: Duration = Duration::from_secs(60);
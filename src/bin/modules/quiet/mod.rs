@@ -0,0 +1,80 @@
+use core::cell::Cell;
+
+use defmt::info;
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embassy_time::Timer;
+
+use crate::modules::{
+    connectivity::clock::get_local_minutes,
+    indicator::{Notification, notify},
+    servo::layers::LAYERS_CHANGED,
+};
+
+use config::{QUIET_END, QUIET_START, SCHEDULE_PERIOD};
+
+pub mod config;
+
+const TAG: &str = "[QUIET]";
+
+/// Lowers the audio gain, slows and shrinks servo moves and drops idle animations and
+/// notifications. Animations asked for on purpose still play.
+static QUIET: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+
+pub fn is_quiet() -> bool {
+    QUIET.lock(|quiet| quiet.get())
+}
+
+pub fn set_quiet(quiet: bool) {
+    if QUIET.lock(|cell| cell.replace(quiet)) == quiet {
+        return;
+    }
+
+    if quiet {
+        info!("{} Quiet", TAG);
+        notify(Notification::QuietOn);
+    } else {
+        info!("{} Not quiet anymore", TAG);
+        notify(Notification::QuietOff);
+    }
+
+    // Idle layers stop or start moving
+    LAYERS_CHANGED.signal(());
+}
+
+/// Returns whether it is quiet now.
+pub fn toggle_quiet() -> bool {
+    let quiet = !is_quiet();
+    set_quiet(quiet);
+    quiet
+}
+
+/// Whether `minutes` since midnight fall in the quiet hours.
+fn is_quiet_time(minutes: u16) -> bool {
+    if QUIET_START <= QUIET_END {
+        (QUIET_START..QUIET_END).contains(&minutes)
+    } else {
+        minutes >= QUIET_START || minutes < QUIET_END
+    }
+}
+
+/// Goes quiet when the quiet hours start and back when they end.
+///
+/// Only follows the start and the end, so toggling it by hand holds until the next one. Does
+/// nothing until the clock was set, which takes the Wi-Fi once in play mode.
+#[embassy_executor::task]
+pub async fn quiet_task() {
+    info!("{} task started", TAG);
+    let mut scheduled = None;
+
+    loop {
+        if let Some(minutes) = get_local_minutes() {
+            let quiet_time = is_quiet_time(minutes);
+            if scheduled != Some(quiet_time) {
+                scheduled = Some(quiet_time);
+                set_quiet(quiet_time);
+            }
+        }
+
+        Timer::after(SCHEDULE_PERIOD).await;
+    }
+}
@@ -1,11 +1,15 @@
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
 use embassy_time::Duration;
+use rgb::RGB8;
 
-use crate::modules::audio::tracks::Tracks;
+use crate::modules::{
+    audio::tracks::Tracks,
+    leds::{LED_GROUP_COUNT, LedGroup},
+};
 
 use super::{
     animations::AnimationType,
-    config::{DEFAULT_BEAK_POSITION, DEFAULT_NECK_POSITION, DEFAULT_WING_POSITION},
+    config::{DEFAULT_BEAK_POSITION, DEFAULT_NECK_POSITION, DEFAULT_WING_POSITION, SERVO_COUNT},
     easing::Easing,
 };
 
@@ -15,9 +19,36 @@ pub const INTERPOLATION_STEPS: u32 = 20;
 pub const FRAME_DURATION: Duration =
     Duration::from_millis(KEYFRAME_DURATION.as_millis() / INTERPOLATION_STEPS as u64);
 
-pub static ANIMATION_QUEUE: Channel<CriticalSectionRawMutex, AnimationType, 4> = Channel::new();
+pub static ANIMATION_QUEUE: Channel<CriticalSectionRawMutex, AnimationRequest, 4> = Channel::new();
+
+#[derive(Clone, Copy)]
+pub struct AnimationRequest {
+    pub animation: AnimationType,
+    /// Asked for on purpose, so it plays in quiet mode too.
+    pub urgent: bool,
+}
+
+impl AnimationRequest {
+    pub const fn new(animation: AnimationType) -> Self {
+        Self {
+            animation,
+            urgent: false,
+        }
+    }
+
+    pub const fn urgent(animation: AnimationType) -> Self {
+        Self {
+            animation,
+            urgent: true,
+        }
+    }
+}
 
-type ServoKeyframe = (u16, Easing);
+/// Servos, then LED groups, each with their own keyframes.
+pub const TRACK_COUNT: usize = SERVO_COUNT + LED_GROUP_COUNT;
+
+pub type ServoKeyframe = (u16, Easing);
+pub type LedKeyframe = (RGB8, Easing);
 type AudioKeyframe = Tracks;
 
 #[derive(Clone)]
@@ -26,6 +57,8 @@ pub struct Frame {
     pub neck_servo: Option<ServoKeyframe>,
     pub wing_right_servo: Option<ServoKeyframe>,
     pub wing_left_servo: Option<ServoKeyframe>,
+    pub eye_leds: Option<LedKeyframe>,
+    pub chest_leds: Option<LedKeyframe>,
     pub audio: Option<AudioKeyframe>,
 }
 
@@ -40,12 +73,39 @@ impl Frame {
         }
     }
 
+    pub fn set_servo(&mut self, servo: usize, keyframe: Option<ServoKeyframe>) {
+        match servo {
+            0 => self.beak_servo = keyframe,
+            1 => self.neck_servo = keyframe,
+            2 => self.wing_right_servo = keyframe,
+            3 => self.wing_left_servo = keyframe,
+            _ => (),
+        }
+    }
+
+    pub fn get_leds(&self, group: LedGroup) -> Option<LedKeyframe> {
+        match group {
+            LedGroup::Eyes => self.eye_leds,
+            LedGroup::Chest => self.chest_leds,
+        }
+    }
+
+    /// Whether the frame has a keyframe for the servo or LED group, see [`TRACK_COUNT`].
+    pub fn has_track(&self, track: usize) -> bool {
+        match LedGroup::get_from_track(track) {
+            Some(group) => self.get_leds(group).is_some(),
+            None => self.get_servo(track).is_some(),
+        }
+    }
+
     pub const fn default() -> Self {
         Self {
             beak_servo: Some((DEFAULT_BEAK_POSITION, Easing::Linear)),
             neck_servo: Some((DEFAULT_NECK_POSITION, Easing::Linear)),
             wing_right_servo: Some((DEFAULT_WING_POSITION, Easing::Linear)),
             wing_left_servo: Some((DEFAULT_WING_POSITION, Easing::Linear)),
+            eye_leds: None,
+            chest_leds: None,
             audio: None,
         }
     }
@@ -56,6 +116,8 @@ impl Frame {
             neck_servo: None,
             wing_right_servo: None,
             wing_left_servo: None,
+            eye_leds: None,
+            chest_leds: None,
             audio: None,
         }
     }
@@ -66,6 +128,8 @@ impl Frame {
             neck_servo: None,
             wing_right_servo: None,
             wing_left_servo: None,
+            eye_leds: None,
+            chest_leds: None,
             audio: Some(track),
         }
     }
@@ -76,6 +140,8 @@ impl Frame {
             neck_servo: None,
             wing_right_servo: None,
             wing_left_servo: None,
+            eye_leds: None,
+            chest_leds: None,
             audio: None,
         }
     }
@@ -0,0 +1,34 @@
+use crate::modules::servo::{
+    animation::{Animation, Frame},
+    config::DEFAULT_NECK_POSITION,
+    easing::Easing,
+    script::Script,
+};
+
+/// Head into the tilt, the other wing out for balance.
+const LEAN: Frame = Frame {
+    beak_servo: None,
+    neck_servo: Some((DEFAULT_NECK_POSITION - 200, Easing::CubicInOut)),
+    wing_right_servo: Some((600, Easing::CubicInOut)),
+    wing_left_servo: None,
+    eye_leds: None,
+    chest_leds: None,
+    audio: None,
+};
+
+const WOBBLE: Frame = Frame {
+    wing_right_servo: Some((400, Easing::CubicInOut)),
+    ..LEAN
+};
+
+const LEAN_LEFT: &Animation = &[
+    Some(Frame::default()),
+    Some(LEAN),
+    Some(WOBBLE),
+    Some(LEAN),
+    Some(Frame::default()),
+];
+
+pub static LEFT: Script = Script::Clip(LEAN_LEFT);
+
+pub static RIGHT: Script = Script::Mirror(&Script::Clip(LEAN_LEFT));
@@ -4,108 +4,57 @@ use crate::modules::{
         animation::{Animation, Frame},
         config::DEFAULT_NECK_POSITION,
         easing::Easing,
+        script::Script,
     },
 };
 
-pub static ANIMATION: &Animation = &[
-    Some(Frame::default()),
-    Some(Frame {
-        beak_servo: Some((0, Easing::Linear)),
-        neck_servo: Some((DEFAULT_NECK_POSITION - 300, Easing::Linear)),
-        wing_right_servo: Some((0, Easing::CubicInOut)),
-        wing_left_servo: Some((0, Easing::CubicInOut)),
-        audio: Some(Tracks::BuboYap1),
-    }),
-    Some(Frame {
-        beak_servo: Some((1000, Easing::Linear)),
-        neck_servo: None,
-        wing_right_servo: Some((1000, Easing::CubicInOut)),
-        wing_left_servo: Some((1000, Easing::CubicInOut)),
-        audio: None,
-    }),
-    Some(Frame {
-        beak_servo: Some((0, Easing::Linear)),
-        neck_servo: Some((DEFAULT_NECK_POSITION + 300, Easing::Linear)),
-        wing_right_servo: Some((0, Easing::CubicInOut)),
-        wing_left_servo: Some((0, Easing::CubicInOut)),
-        audio: None,
-    }),
-    Some(Frame {
-        beak_servo: Some((1000, Easing::Linear)),
-        neck_servo: None,
-        wing_right_servo: Some((1000, Easing::CubicInOut)),
-        wing_left_servo: Some((1000, Easing::CubicInOut)),
-        audio: None,
-    }),
-    Some(Frame {
-        beak_servo: Some((0, Easing::Linear)),
-        neck_servo: Some((DEFAULT_NECK_POSITION - 300, Easing::Linear)),
-        wing_right_servo: Some((0, Easing::CubicInOut)),
-        wing_left_servo: Some((0, Easing::CubicInOut)),
-        audio: None,
-    }),
-    Some(Frame {
-        beak_servo: Some((1000, Easing::Linear)),
-        neck_servo: None,
-        wing_right_servo: Some((1000, Easing::CubicInOut)),
-        wing_left_servo: Some((1000, Easing::CubicInOut)),
-        audio: None,
-    }),
-    Some(Frame {
-        beak_servo: Some((0, Easing::Linear)),
-        neck_servo: Some((DEFAULT_NECK_POSITION + 300, Easing::Linear)),
-        wing_right_servo: Some((0, Easing::CubicInOut)),
-        wing_left_servo: Some((0, Easing::CubicInOut)),
-        audio: None,
-    }),
-    Some(Frame {
-        beak_servo: Some((1000, Easing::Linear)),
-        neck_servo: None,
-        wing_right_servo: Some((1000, Easing::CubicInOut)),
-        wing_left_servo: Some((1000, Easing::CubicInOut)),
-        audio: None,
-    }),
-    Some(Frame {
-        beak_servo: Some((0, Easing::Linear)),
-        neck_servo: Some((DEFAULT_NECK_POSITION - 300, Easing::Linear)),
-        wing_right_servo: Some((0, Easing::CubicInOut)),
-        wing_left_servo: Some((0, Easing::CubicInOut)),
-        audio: None,
-    }),
-    Some(Frame {
-        beak_servo: Some((1000, Easing::Linear)),
-        neck_servo: None,
-        wing_right_servo: Some((1000, Easing::CubicInOut)),
-        wing_left_servo: Some((1000, Easing::CubicInOut)),
-        audio: None,
-    }),
-    Some(Frame {
-        beak_servo: Some((0, Easing::Linear)),
-        neck_servo: Some((DEFAULT_NECK_POSITION + 300, Easing::Linear)),
-        wing_right_servo: Some((0, Easing::CubicInOut)),
-        wing_left_servo: Some((0, Easing::CubicInOut)),
-        audio: Some(Tracks::BuboYap2),
-    }),
-    Some(Frame {
-        beak_servo: Some((1000, Easing::Linear)),
-        neck_servo: None,
-        wing_right_servo: Some((1000, Easing::CubicInOut)),
-        wing_left_servo: Some((1000, Easing::CubicInOut)),
-        audio: None,
-    }),
-    Some(Frame {
-        beak_servo: Some((0, Easing::Linear)),
-        neck_servo: Some((DEFAULT_NECK_POSITION - 300, Easing::Linear)),
-        wing_right_servo: Some((0, Easing::CubicInOut)),
-        wing_left_servo: Some((0, Easing::CubicInOut)),
-        audio: None,
-    }),
-    Some(Frame {
-        beak_servo: Some((1000, Easing::Linear)),
-        neck_servo: None,
-        wing_right_servo: Some((1000, Easing::CubicInOut)),
-        wing_left_servo: Some((1000, Easing::CubicInOut)),
-        audio: None,
-    }),
-    Some(Frame::default()),
-];
+const LOOK_LEFT: Frame = Frame {
+    beak_servo: Some((0, Easing::Linear)),
+    neck_servo: Some((DEFAULT_NECK_POSITION - 300, Easing::Linear)),
+    wing_right_servo: Some((0, Easing::CubicInOut)),
+    wing_left_servo: Some((0, Easing::CubicInOut)),
+    eye_leds: None,
+    chest_leds: None,
+    audio: None,
+};
+
+const FLAP: Frame = Frame {
+    beak_servo: Some((1000, Easing::Linear)),
+    neck_servo: None,
+    wing_right_servo: Some((1000, Easing::CubicInOut)),
+    wing_left_servo: Some((1000, Easing::CubicInOut)),
+    eye_leds: None,
+    chest_leds: None,
+    audio: None,
+};
+
+const REST: &Animation = &[Some(Frame::default())];
+
+const SWAY_LEFT: &Animation = &[Some(LOOK_LEFT), Some(FLAP)];
+
+/// Right, then left.
+const SWAY: Script = Script::Sequence(&[
+    Script::Mirror(&Script::Clip(SWAY_LEFT)),
+    Script::Clip(SWAY_LEFT),
+]);
+
+pub static SCRIPT: Script = Script::Sequence(&[
+    Script::Clip(REST),
+    Script::Clip(&[
+        Some(Frame {
+            audio: Some(Tracks::BuboYap1),
+            ..LOOK_LEFT
+        }),
+        Some(FLAP),
+    ]),
+    Script::Repeat(2, &SWAY),
+    Script::Mirror(&Script::Clip(&[
+        Some(Frame {
+            audio: Some(Tracks::BuboYap2),
+            ..LOOK_LEFT
+        }),
+        Some(FLAP),
+    ])),
+    Script::Clip(SWAY_LEFT),
+    Script::Clip(REST),
+]);
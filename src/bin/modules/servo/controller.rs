@@ -1,5 +1,6 @@
 use defmt::{info, warn};
-use embassy_time::{Duration, Timer};
+use embassy_futures::select::{Either4, select4};
+use embassy_time::{Duration, Instant, Ticker, Timer};
 use esp_hal::{
     gpio::OutputPin,
     mcpwm::{operator::PwmPinConfig, timer::PwmWorkingMode, McPwm, PeripheralClockConfig},
@@ -9,15 +10,27 @@ use esp_hal::{
 
 use crate::modules::{
     audio::AUDIO_QUEUE,
+    crash::set_current_animation,
+    leds::{LedGroup, set_group},
+    power::report_activity,
+    quiet::{
+        config::{QUIET_AMPLITUDE, QUIET_MAX_STEP},
+        is_quiet,
+    },
     servo::{
         animation::{FRAME_DURATION, INTERPOLATION_STEPS},
-        config::{SERVO_MAX, SERVO_MIN},
+        config::{SERVO_MAX, SERVO_MIN, SERVOS},
+        layers::{self, LAYERS_CHANGED},
+        live::{LIVE_SERVO_QUEUE, LIVE_TIMEOUT, LiveTargets, MAX_SLEW_PER_FRAME, slew},
+        recorder::{RECORD_QUEUE, RecordCommand, Recorder},
+        recordings,
     },
     util::map_range_clamped,
+    watchdog::{SupervisedTask, check_in, idle},
 };
 
 use super::{
-    animation::{Animation, ANIMATION_QUEUE},
+    animation::{Animation, ANIMATION_QUEUE, TRACK_COUNT},
     config::SERVO_COUNT,
     easing::Easing,
     Servo,
@@ -25,9 +38,19 @@ use super::{
 
 pub struct ServoController {
     servos: [Servo<'static>; SERVO_COUNT],
+    // Last commanded position of every servo, before layers are blended in
+    positions: [u16; SERVO_COUNT],
+    // Servos driven by an animation or live control
+    engaged: [bool; SERVO_COUNT],
+    // Servos driven by a layer on the last update
+    layered: [bool; SERVO_COUNT],
+    // Last position written to every servo
+    outputs: [u16; SERVO_COUNT],
+    recorder: Option<Recorder>,
 }
 
 use num_traits::float::FloatCore;
+use rgb::RGB8;
 
 const TAG: &str = "[SERVO]";
 
@@ -79,7 +102,14 @@ impl ServoController {
 
         mcpwm.timer0.start(timer_clock_cfg);
 
-        let mut controller = ServoController { servos };
+        let mut controller = ServoController {
+            servos,
+            positions: SERVOS.map(|servo| servo.default_position),
+            engaged: [false; SERVO_COUNT],
+            layered: [false; SERVO_COUNT],
+            outputs: SERVOS.map(|servo| servo.default_position),
+            recorder: None,
+        };
 
         // Set default positions
         controller.reset_servos();
@@ -93,8 +123,35 @@ impl ServoController {
     }
 
     pub async fn run_loop(&mut self) {
-        let animation = ANIMATION_QUEUE.receive().await;
-        self.run_animation(animation.get_animation()).await;
+        // Keep layers moving while nothing else is playing
+        let layers_idle = async {
+            if layers::is_active() {
+                Timer::after(FRAME_DURATION).await;
+            } else {
+                LAYERS_CHANGED.wait().await;
+            }
+        };
+
+        let next = select4(
+            ANIMATION_QUEUE.receive(),
+            LIVE_SERVO_QUEUE.wait(),
+            RECORD_QUEUE.wait(),
+            layers_idle,
+        );
+
+        match idle(SupervisedTask::Servo, next).await {
+            Either4::First(request) if is_quiet() && !request.urgent => {
+                info!("{} Quiet, skipping '{}'", TAG, request.animation.get_name());
+            }
+            Either4::First(request) => {
+                set_current_animation(Some(request.animation.get_name()));
+                self.run_animation(&request.animation.get_animation()).await;
+                set_current_animation(None);
+            }
+            Either4::Second(targets) => self.run_live(targets).await,
+            Either4::Third(command) => self.handle_record_command(command),
+            Either4::Fourth(_) => self.update_outputs(),
+        }
     }
 
     // Control
@@ -111,21 +168,85 @@ impl ServoController {
             );
             servo.set_timestamp(position as u16);
         }
+        self.positions = SERVOS.map(|servo| servo.default_position);
+        self.outputs = self.positions;
+    }
+
+    /// Set the position of a servo, it is written on the next [`Self::update_outputs`].
+    fn move_servo(&mut self, servo_index: usize, position: u16) {
+        self.positions[servo_index] = position;
+        self.engaged[servo_index] = true;
+    }
+
+    /// Blend the layers over the servo positions and write the result.
+    fn update_outputs(&mut self) {
+        check_in(SupervisedTask::Servo);
+        let blended = layers::blend(self.positions, FRAME_DURATION);
+
+        for servo_index in 0..SERVO_COUNT {
+            match blended[servo_index] {
+                Some(position) => self.write_servo(servo_index, position),
+                None if self.engaged[servo_index] => {
+                    self.write_servo(servo_index, self.positions[servo_index])
+                }
+                // Release servos when a layer stops driving them
+                None if self.layered[servo_index] => self.servos[servo_index].set_timestamp(0),
+                None => (),
+            }
+
+            self.layered[servo_index] = blended[servo_index].is_some();
+        }
+    }
+
+    /// Every move goes through here, so quiet mode holds whatever drives the servo.
+    fn write_servo(&mut self, servo_index: usize, position: u16) {
+        let position = if is_quiet() {
+            // Closer to the default position, and slower to get there
+            let default = SERVOS[servo_index].default_position as f32;
+            let scaled = default + (position as f32 - default) * QUIET_AMPLITUDE;
+            slew(
+                self.outputs[servo_index],
+                libm::roundf(scaled) as u16,
+                QUIET_MAX_STEP,
+            )
+        } else {
+            position
+        };
+
+        self.servos[servo_index].move_to(position);
+        self.outputs[servo_index] = position;
+    }
+
+    /// Stop whatever was playing and leave the servos released in their default positions.
+    pub async fn park(&mut self) {
+        info!("{} Parking servos", TAG);
+        set_current_animation(None);
+        self.reset_servos();
+        Timer::after(Duration::from_millis(500)).await;
+        self.release_servos();
     }
 
     fn release_servos(&mut self) {
         for servo in &mut self.servos {
             servo.set_timestamp(0);
         }
+        self.engaged = [false; SERVO_COUNT];
+        self.layered = [false; SERVO_COUNT];
     }
 
     // Animation
     pub async fn run_animation(&mut self, animation: &Animation) {
+        if animation.is_empty() {
+            warn!("{} Skipping empty animation", TAG);
+            return;
+        }
+
         info!("{} Running animation with {} frames", TAG, animation.len());
+        report_activity();
         let total_frames = animation.len();
 
-        let mut previous_servo_frame_index: [Option<usize>; SERVO_COUNT] = [None; SERVO_COUNT];
-        let mut next_servo_frame_index: [Option<usize>; SERVO_COUNT] = [None; SERVO_COUNT];
+        let mut previous_frame_index: [Option<usize>; TRACK_COUNT] = [None; TRACK_COUNT];
+        let mut next_frame_index: [Option<usize>; TRACK_COUNT] = [None; TRACK_COUNT];
 
         for frame_index in 0..animation.len() {
             // Play audio if present
@@ -137,8 +258,8 @@ impl ServoController {
 
             // Get the frames to interpolate from
             ServoController::get_surrounding_frame_indices(
-                &mut next_servo_frame_index,
-                &mut previous_servo_frame_index,
+                &mut next_frame_index,
+                &mut previous_frame_index,
                 animation,
                 frame_index,
             );
@@ -150,9 +271,15 @@ impl ServoController {
                         .unwrap()
                         .get_servo(servo_index)
                     {
-                        self.servos[servo_index].set_timestamp(target.0);
+                        self.move_servo(servo_index, target.0);
                     }
                 }
+                for group in LedGroup::ALL {
+                    if let Some(target) = animation[frame_index].as_ref().unwrap().get_leds(group) {
+                        set_group(group, target.0);
+                    }
+                }
+                self.update_outputs();
                 break;
             }
 
@@ -160,7 +287,7 @@ impl ServoController {
             for interpolation_index in 0..INTERPOLATION_STEPS {
                 // Set servo angles
                 for servo_index in 0..SERVO_COUNT {
-                    let from = match previous_servo_frame_index[servo_index] {
+                    let from = match previous_frame_index[servo_index] {
                         Some(index) => Some((
                             animation[index]
                                 .as_ref()
@@ -173,7 +300,7 @@ impl ServoController {
                         None => None,
                     };
 
-                    let to = match next_servo_frame_index[servo_index] {
+                    let to = match next_frame_index[servo_index] {
                         Some(index) => {
                             let frame = animation[index]
                                 .as_ref()
@@ -194,23 +321,48 @@ impl ServoController {
                             continue;
                         }
 
-                        let frame_span = to_index - from_index;
-                        let total_keyframe_steps = INTERPOLATION_STEPS * frame_span as u32;
-                        let current_frame_in_span = (frame_index - from_index) as u32;
-                        let current_step =
-                            current_frame_in_span * INTERPOLATION_STEPS + interpolation_index + 1;
-
-                        let t = current_step as f32 / total_keyframe_steps as f32;
+                        let t = Self::keyframe_progress(
+                            from_index,
+                            to_index,
+                            frame_index,
+                            interpolation_index,
+                        );
                         let target = Self::interpolate(from_value, to_value, t, &easing);
 
                         if (t > 1.0) || (t < 0.0) {
                             warn!("{} Interpolation out of bounds: t = {}", TAG, t);
                         }
 
-                        self.servos[servo_index].move_to(target);
+                        self.move_servo(servo_index, target);
                     }
                 }
 
+                // Fade the LEDs
+                for group in LedGroup::ALL {
+                    let track = group.get_track();
+                    let (Some(from_index), Some(to_index)) =
+                        (previous_frame_index[track], next_frame_index[track])
+                    else {
+                        continue;
+                    };
+
+                    let from = animation[from_index].as_ref().unwrap().get_leds(group);
+                    let to = animation[to_index].as_ref().unwrap().get_leds(group);
+                    if let (Some((from_color, _)), Some((to_color, easing))) = (from, to) {
+                        let t = Self::keyframe_progress(
+                            from_index,
+                            to_index,
+                            frame_index,
+                            interpolation_index,
+                        );
+                        set_group(
+                            group,
+                            Self::interpolate_color(from_color, to_color, t, &easing),
+                        );
+                    }
+                }
+
+                self.update_outputs();
                 Timer::after(FRAME_DURATION).await;
             }
         }
@@ -219,18 +371,98 @@ impl ServoController {
         self.release_servos();
     }
 
+    // Live control
+    pub async fn run_live(&mut self, initial_targets: LiveTargets) {
+        info!("{} Live control started", TAG);
+        report_activity();
+
+        let mut targets = self.positions;
+        let mut update = Some(initial_targets);
+        let mut last_command = Instant::now();
+        let mut ticker = Ticker::every(FRAME_DURATION);
+        let mut step: u32 = 0;
+
+        loop {
+            if let Some(new_targets) = update.take() {
+                for (target, new_target) in targets.iter_mut().zip(new_targets) {
+                    if let Some(new_target) = new_target {
+                        *target = new_target;
+                    }
+                }
+                last_command = Instant::now();
+            }
+
+            if Instant::now() - last_command > LIVE_TIMEOUT {
+                break;
+            }
+
+            for servo_index in 0..SERVO_COUNT {
+                let position = self.positions[servo_index];
+                let next = slew(position, targets[servo_index], MAX_SLEW_PER_FRAME);
+                self.move_servo(servo_index, next);
+            }
+
+            self.update_outputs();
+
+            // Record once per keyframe
+            if step % INTERPOLATION_STEPS == 0 {
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.sample(self.positions);
+                }
+            }
+
+            ticker.next().await;
+            step = step.wrapping_add(1);
+            update = LIVE_SERVO_QUEUE.try_take();
+
+            if let Some(command) = RECORD_QUEUE.try_take() {
+                self.handle_record_command(command);
+            }
+        }
+
+        info!("{} Live control timed out", TAG);
+        self.release_servos();
+    }
+
+    // Recording
+    fn handle_record_command(&mut self, command: RecordCommand) {
+        match command {
+            RecordCommand::Start => {
+                info!("{} Recording started", TAG);
+                self.recorder = Some(Recorder::new(self.positions));
+            }
+            RecordCommand::Stop(name) => {
+                let Some(recorder) = self.recorder.take() else {
+                    warn!("{} Not recording", TAG);
+                    return;
+                };
+
+                let frames = recorder.finish();
+                let keyframes = frames.iter().filter(|frame| frame.is_some()).count();
+
+                match recordings::save(name, frames) {
+                    Some(index) => info!(
+                        "{} Saved recording {} with {} keyframes",
+                        TAG, index, keyframes
+                    ),
+                    None => warn!("{} No free recording slots", TAG),
+                }
+            }
+        }
+    }
+
     fn get_surrounding_frame_indices(
-        next_servo_frame_index: &mut [Option<usize>; SERVO_COUNT],
-        previous_servo_frame_index: &mut [Option<usize>; SERVO_COUNT],
+        next_frame_index: &mut [Option<usize>; TRACK_COUNT],
+        previous_frame_index: &mut [Option<usize>; TRACK_COUNT],
         animation: &Animation,
         frame_index: usize,
     ) {
-        for servo_index in 0..SERVO_COUNT {
-            // Previous servo frame
+        for track in 0..TRACK_COUNT {
+            // Previous keyframe
             let mut should_fetch_previous = false;
 
             // if we exceeded the last known previous index, we need to fetch the previous one
-            if let Some(last_known_previous_index) = previous_servo_frame_index[servo_index] {
+            if let Some(last_known_previous_index) = previous_frame_index[track] {
                 if last_known_previous_index < frame_index && frame_index != animation.len() - 1 {
                     should_fetch_previous = true;
                 }
@@ -242,20 +474,19 @@ impl ServoController {
             }
 
             if should_fetch_previous {
-                previous_servo_frame_index[servo_index] =
-                    ServoController::get_closest_servo_keyframe_index(
-                        animation,
-                        frame_index,
-                        servo_index,
-                        false,
-                    );
+                previous_frame_index[track] = ServoController::get_closest_keyframe_index(
+                    animation,
+                    frame_index,
+                    track,
+                    false,
+                );
             }
 
-            // Next servo frame
+            // Next keyframe
             let mut should_fetch_next = false;
 
             // If we hit the last known next index, we need to fetch the next one
-            if let Some(last_known_next_index) = next_servo_frame_index[servo_index] {
+            if let Some(last_known_next_index) = next_frame_index[track] {
                 if last_known_next_index == frame_index
                     && last_known_next_index != animation.len() - 1
                 {
@@ -268,23 +499,22 @@ impl ServoController {
                 should_fetch_next = true;
             }
 
-            // Get the next servo frame
+            // Get the next keyframe
             if should_fetch_next {
-                next_servo_frame_index[servo_index] =
-                    ServoController::get_closest_servo_keyframe_index(
-                        animation,
-                        frame_index + 1,
-                        servo_index,
-                        true,
-                    );
+                next_frame_index[track] = ServoController::get_closest_keyframe_index(
+                    animation,
+                    frame_index + 1,
+                    track,
+                    true,
+                );
             }
         }
     }
 
-    fn get_closest_servo_keyframe_index(
+    fn get_closest_keyframe_index(
         animation: &Animation,
         start_index: usize,
-        servo_index: usize,
+        track: usize,
         forward: bool,
     ) -> Option<usize> {
         let len = animation.len();
@@ -296,15 +526,7 @@ impl ServoController {
             }
 
             if let Some(frame) = &animation[index] {
-                let keyframe = match servo_index {
-                    0 => frame.beak_servo,
-                    1 => frame.neck_servo,
-                    2 => frame.wing_right_servo,
-                    3 => frame.wing_left_servo,
-                    _ => None,
-                };
-
-                if keyframe.is_some() {
+                if frame.has_track(track) {
                     return Some(index);
                 }
             }
@@ -325,10 +547,34 @@ impl ServoController {
         None
     }
 
+    /// Progress from the keyframe at `from_index` to the one at `to_index`, from 0 to 1.
+    fn keyframe_progress(
+        from_index: usize,
+        to_index: usize,
+        frame_index: usize,
+        interpolation_index: u32,
+    ) -> f32 {
+        let frame_span = to_index - from_index;
+        let total_keyframe_steps = INTERPOLATION_STEPS * frame_span as u32;
+        let current_frame_in_span = (frame_index - from_index) as u32;
+        let current_step = current_frame_in_span * INTERPOLATION_STEPS + interpolation_index + 1;
+
+        current_step as f32 / total_keyframe_steps as f32
+    }
+
     fn interpolate(from: u16, to: u16, t: f32, easing: &Easing) -> u16 {
         let eased_t = easing.ease(t);
         let delta = to as f32 - from as f32;
         let interpolated_value = from as f32 + (delta * eased_t);
         interpolated_value.round() as u16
     }
+
+    fn interpolate_color(from: RGB8, to: RGB8, t: f32, easing: &Easing) -> RGB8 {
+        let channel = |from: u8, to: u8| Self::interpolate(from as u16, to as u16, t, easing) as u8;
+        RGB8::new(
+            channel(from.r, to.r),
+            channel(from.g, to.g),
+            channel(from.b, to.b),
+        )
+    }
 }
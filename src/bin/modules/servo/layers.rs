@@ -0,0 +1,249 @@
+use core::cell::{Cell, RefCell};
+use core::f32::consts::PI;
+
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    signal::Signal,
+};
+use embassy_time::{Duration, Instant};
+
+use crate::modules::quiet::is_quiet;
+
+use super::config::{DEFAULT_WING_POSITION, SERVO_COUNT, SERVO_MAX, SERVOS};
+
+pub const LAYER_COUNT: usize = 3;
+
+/// Raised when a layer is changed, wakes the servo controller when it is idle.
+pub static LAYERS_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+static LAYERS: Mutex<CriticalSectionRawMutex, RefCell<[Layer; LAYER_COUNT]>> =
+    Mutex::new(RefCell::new([
+        // Wings slowly moving up and down
+        Layer::new(
+            mask(&[2, 3]),
+            BlendMode::Additive,
+            LayerSource::Wave {
+                amplitude: 80,
+                period: Duration::from_secs(4),
+            },
+        ),
+        // Neck offset, set by whatever is tracking
+        Layer::new(mask(&[1]), BlendMode::Additive, LayerSource::Value(0)),
+        // Holds the wings in, animations only move them as far as the weight lets them
+        Layer::new(
+            mask(&[2, 3]),
+            BlendMode::Override,
+            LayerSource::Value(DEFAULT_WING_POSITION as i16),
+        ),
+    ]));
+
+/// Layers keep their weights while suspended, but don't drive any servo.
+static SUSPENDED: Mutex<CriticalSectionRawMutex, Cell<bool>> = Mutex::new(Cell::new(false));
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LayerId {
+    Breathing = 0,
+    HeadTracking = 1,
+    FoldedWings = 2,
+}
+
+impl LayerId {
+    /// Moving on their own rather than following something, see [`is_quiet`].
+    const IDLE: [LayerId; 1] = [LayerId::Breathing];
+
+    fn is_idle(index: usize) -> bool {
+        LayerId::IDLE.iter().any(|idle| *idle as usize == index)
+    }
+
+    pub fn get_from_binary(payload: &[u8]) -> Option<LayerId> {
+        match payload {
+            b"breathing" => Some(LayerId::Breathing),
+            b"head_tracking" => Some(LayerId::HeadTracking),
+            b"folded_wings" => Some(LayerId::FoldedWings),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum BlendMode {
+    /// Blend from the animation towards the layer by its weight.
+    Override,
+    /// Add the layer on top of the animation, scaled by its weight.
+    Additive,
+}
+
+#[derive(Clone, Copy)]
+pub enum LayerSource {
+    /// Rises by `amplitude` and back once per period, from the default position of every
+    /// servo for override layers.
+    Wave { amplitude: u16, period: Duration },
+    /// Position for override layers, offset for additive layers.
+    Value(i16),
+}
+
+#[derive(Clone, Copy)]
+pub struct Layer {
+    /// One bit per servo the layer applies to.
+    mask: u8,
+    mode: BlendMode,
+    source: LayerSource,
+    weight: f32,
+    target_weight: f32,
+    /// Time to fade from 0 to full weight.
+    fade: Duration,
+}
+
+impl Layer {
+    const fn new(mask: u8, mode: BlendMode, source: LayerSource) -> Self {
+        Self {
+            mask,
+            mode,
+            source,
+            weight: 0.0,
+            target_weight: 0.0,
+            fade: Duration::from_millis(0),
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.weight > 0.0 || self.target_weight > 0.0
+    }
+
+    fn update_weight(&mut self, elapsed: Duration) {
+        if self.fade.as_ticks() == 0 {
+            self.weight = self.target_weight;
+            return;
+        }
+
+        let step = elapsed.as_ticks() as f32 / self.fade.as_ticks() as f32;
+        self.weight = if self.weight < self.target_weight {
+            (self.weight + step).min(self.target_weight)
+        } else {
+            (self.weight - step).max(self.target_weight)
+        };
+    }
+
+    /// Value of the layer for a servo, as a position or an offset depending on the mode.
+    fn sample(&self, servo_index: usize, now: Instant) -> f32 {
+        let base = match self.mode {
+            BlendMode::Override => SERVOS[servo_index].default_position as f32,
+            BlendMode::Additive => 0.0,
+        };
+
+        match self.source {
+            LayerSource::Wave { amplitude, period } => {
+                let phase = (now.as_ticks() % period.as_ticks()) as f32 / period.as_ticks() as f32;
+                base + amplitude as f32 * (1.0 - libm::cosf(2.0 * PI * phase)) / 2.0
+            }
+            LayerSource::Value(value) => value as f32,
+        }
+    }
+}
+
+const fn mask(servos: &[usize]) -> u8 {
+    let mut mask = 0;
+    let mut i = 0;
+    while i < servos.len() {
+        mask |= 1 << servos[i];
+        i += 1;
+    }
+    mask
+}
+
+/// Fade a layer to `weight` (0.0 to 1.0) over `fade`.
+pub fn set_weight(layer: LayerId, weight: f32, fade: Duration) {
+    LAYERS.lock(|layers| {
+        let layer = &mut layers.borrow_mut()[layer as usize];
+        layer.target_weight = weight.clamp(0.0, 1.0);
+        layer.fade = fade;
+    });
+    LAYERS_CHANGED.signal(());
+}
+
+/// Set the value of a layer with a [`LayerSource::Value`] source.
+pub fn set_value(layer: LayerId, value: i16) {
+    LAYERS.lock(|layers| {
+        let layer = &mut layers.borrow_mut()[layer as usize];
+        if let LayerSource::Value(_) = layer.source {
+            layer.source = LayerSource::Value(value);
+        }
+    });
+}
+
+/// Stop all layers, so the servos can be released.
+pub fn set_suspended(suspended: bool) {
+    SUSPENDED.lock(|cell| cell.set(suspended));
+    LAYERS_CHANGED.signal(());
+}
+
+fn is_suspended() -> bool {
+    SUSPENDED.lock(|cell| cell.get())
+}
+
+/// Whether a layer moves any servo, idle layers don't while quiet.
+pub fn is_active() -> bool {
+    if is_suspended() {
+        return false;
+    }
+
+    let quiet = is_quiet();
+    LAYERS.lock(|layers| {
+        layers
+            .borrow()
+            .iter()
+            .enumerate()
+            .any(|(index, layer)| layer.is_active() && !(quiet && LayerId::is_idle(index)))
+    })
+}
+
+/// Advance the layer fades by `elapsed` and blend them over `base`.
+///
+/// Returns the final position of every servo a layer is active on.
+pub fn blend(base: [u16; SERVO_COUNT], elapsed: Duration) -> [Option<u16>; SERVO_COUNT] {
+    let now = Instant::now();
+    let mut output: [Option<u16>; SERVO_COUNT] = [None; SERVO_COUNT];
+
+    if is_suspended() {
+        return output;
+    }
+
+    let quiet = is_quiet();
+
+    LAYERS.lock(|layers| {
+        let mut layers = layers.borrow_mut();
+
+        for layer in layers.iter_mut() {
+            layer.update_weight(elapsed);
+        }
+
+        for (servo_index, output) in output.iter_mut().enumerate() {
+            let mut value = base[servo_index] as f32;
+            let mut layered = false;
+
+            for (layer_index, layer) in layers.iter().enumerate() {
+                if layer.mask & (1 << servo_index) == 0 || !layer.is_active() {
+                    continue;
+                }
+
+                // Idle layers keep their weight, but stay still while quiet
+                if quiet && LayerId::is_idle(layer_index) {
+                    continue;
+                }
+
+                let sample = layer.sample(servo_index, now);
+                value = match layer.mode {
+                    BlendMode::Override => value + (sample - value) * layer.weight,
+                    BlendMode::Additive => value + sample * layer.weight,
+                };
+                layered = true;
+            }
+
+            if layered {
+                *output = Some(libm::roundf(value).clamp(0.0, SERVO_MAX as f32) as u16);
+            }
+        }
+    });
+
+    output
+}
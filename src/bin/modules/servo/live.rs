@@ -0,0 +1,48 @@
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::Duration;
+
+use super::config::{SERVO_COUNT, SERVO_MAX};
+
+/// Servo targets for live control, `None` keeps the servo where it is.
+pub type LiveTargets = [Option<u16>; SERVO_COUNT];
+
+/// Latest live targets, newer commands overwrite older ones that were not applied yet.
+pub static LIVE_SERVO_QUEUE: Signal<CriticalSectionRawMutex, LiveTargets> = Signal::new();
+
+/// Hand control back to the animation engine after this long without a command.
+pub const LIVE_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Maximum movement per frame, 40 units per 12ms moves the full range in about 300ms.
+pub const MAX_SLEW_PER_FRAME: u16 = 40;
+
+/// Marks a servo as unchanged in a packed target array.
+const UNCHANGED: u16 = u16::MAX;
+
+/// Parse a packed target array.
+///
+/// The payload holds one little-endian `u16` per servo in the order beak, neck, right wing,
+/// left wing. `0xFFFF` leaves the servo unchanged.
+pub fn parse_targets(payload: &[u8]) -> Option<LiveTargets> {
+    if payload.len() != SERVO_COUNT * 2 {
+        return None;
+    }
+
+    let mut targets: LiveTargets = [None; SERVO_COUNT];
+    for (target, bytes) in targets.iter_mut().zip(payload.chunks_exact(2)) {
+        let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+        if value != UNCHANGED {
+            *target = Some(value.min(SERVO_MAX as u16));
+        }
+    }
+
+    Some(targets)
+}
+
+/// Move `from` towards `to` by at most `max_step`.
+pub fn slew(from: u16, to: u16, max_step: u16) -> u16 {
+    if to > from {
+        from + (to - from).min(max_step)
+    } else {
+        from - (from - to).min(max_step)
+    }
+}
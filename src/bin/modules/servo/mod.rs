@@ -1,23 +1,40 @@
 use controller::ServoController;
 use defmt::info;
+use embassy_futures::select::{Either, select};
 use esp_hal::{mcpwm::operator::PwmPin, peripherals::MCPWM0};
 
-use crate::modules::util::map_range_clamped;
+use crate::modules::{
+    power::PAUSED,
+    util::map_range_clamped,
+    watchdog::{SupervisedTask, idle},
+};
 
 pub mod animation;
 pub mod animations;
 pub mod config;
 pub mod controller;
 pub mod easing;
+pub mod layers;
+pub mod live;
+pub mod recorder;
+pub mod recordings;
+pub mod script;
 
 const TAG: &str = "[MQTT]";
 
 #[embassy_executor::task]
 pub async fn servo_task(mut controller: ServoController) {
     info!("{} Task started", TAG);
+    let mut paused = PAUSED.receiver().unwrap();
 
     loop {
-        controller.run_loop().await;
+        let pause = paused.get_and(|paused| *paused);
+        if let Either::Second(_) = select(controller.run_loop(), pause).await {
+            controller.park().await;
+            idle(SupervisedTask::Servo, paused.get_and(|paused| !*paused)).await;
+            // Nothing asked for while paused is still wanted
+            animation::ANIMATION_QUEUE.clear();
+        }
     }
 }
 
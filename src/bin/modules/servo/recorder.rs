@@ -0,0 +1,189 @@
+use alloc::{string::String, vec, vec::Vec};
+use defmt::warn;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::Instant;
+
+use crate::modules::audio::{AudioSource, NOW_PLAYING};
+
+use super::{
+    animation::{Frame, KEYFRAME_DURATION},
+    config::SERVO_COUNT,
+    easing::Easing,
+};
+
+const TAG: &str = "[RECORDER]";
+
+pub static RECORD_QUEUE: Signal<CriticalSectionRawMutex, RecordCommand> = Signal::new();
+
+/// Longest recording, 240 keyframes of 250ms is one minute.
+pub const MAX_RECORDING_FRAMES: usize = 240;
+
+/// Maximum error in servo units before a sample is kept as a keyframe.
+pub const KEYFRAME_TOLERANCE: u16 = 15;
+
+pub enum RecordCommand {
+    Start,
+    Stop(String),
+}
+
+impl RecordCommand {
+    /// Parse `start` or `stop:<name>`.
+    pub fn get_from_binary(payload: &[u8]) -> Option<RecordCommand> {
+        match payload {
+            b"start" => Some(RecordCommand::Start),
+            _ => {
+                let name = payload.strip_prefix(b"stop:")?;
+                let name = core::str::from_utf8(name).ok()?;
+                if name.is_empty() {
+                    return None;
+                }
+                Some(RecordCommand::Stop(String::from(name)))
+            }
+        }
+    }
+}
+
+/// Records live servo positions once per keyframe.
+pub struct Recorder {
+    started: Instant,
+    samples: Vec<[u16; SERVO_COUNT]>,
+    audio: Vec<(usize, AudioSource)>,
+    last_playback: Option<Instant>,
+}
+
+impl Recorder {
+    /// Start from where the servos are now, the first frame holds `positions`.
+    pub fn new(positions: [u16; SERVO_COUNT]) -> Self {
+        Self {
+            started: Instant::now(),
+            samples: vec![positions],
+            audio: Vec::new(),
+            last_playback: None,
+        }
+    }
+
+    pub fn sample(&mut self, positions: [u16; SERVO_COUNT]) {
+        let frame_index = Self::frame_index(self.started, Instant::now());
+
+        if frame_index >= MAX_RECORDING_FRAMES {
+            return;
+        }
+
+        // Hold the last position over gaps between live sessions
+        while self.samples.len() < frame_index {
+            let last = self.samples.last().copied().unwrap_or(positions);
+            self.samples.push(last);
+        }
+
+        if self.samples.len() == frame_index {
+            self.samples.push(positions);
+        }
+
+        self.sample_audio();
+    }
+
+    fn sample_audio(&mut self) {
+        let Some(playback) = NOW_PLAYING.lock(|now_playing| now_playing.get()) else {
+            return;
+        };
+
+        if self.last_playback == Some(playback.started) {
+            return;
+        }
+
+        // A frame can only start a track from the beginning, so audio that was already playing
+        // would be out of sync
+        if playback.started >= self.started {
+            let frame_index = Self::frame_index(self.started, playback.started);
+            self.audio.push((frame_index, playback.source));
+        }
+
+        self.last_playback = Some(playback.started);
+    }
+
+    /// Reduce the recorded samples to keyframes.
+    pub fn finish(self) -> Vec<Option<Frame>> {
+        let len = self.samples.len();
+        let mut frames: Vec<Option<Frame>> = vec![None; len];
+
+        if len == 0 {
+            return frames;
+        }
+
+        let mut keep = vec![false; len];
+        let mut channel = Vec::with_capacity(len);
+
+        for servo_index in 0..SERVO_COUNT {
+            channel.clear();
+            channel.extend(self.samples.iter().map(|sample| sample[servo_index]));
+            keep.fill(false);
+
+            reduce(&channel, KEYFRAME_TOLERANCE, &mut keep);
+
+            for (frame_index, _) in keep.iter().enumerate().filter(|(_, keep)| **keep) {
+                let frame = frames[frame_index].get_or_insert_with(Frame::empty);
+                frame.set_servo(servo_index, Some((channel[frame_index], Easing::Linear)));
+            }
+        }
+
+        for (frame_index, source) in self.audio {
+            match source {
+                AudioSource::Track(track) => {
+                    let frame_index = frame_index.min(len - 1);
+                    frames[frame_index].get_or_insert_with(Frame::empty).audio = Some(track);
+                }
+                AudioSource::Stream => {
+                    warn!(
+                        "{} Stream at frame {} can't be stored in an animation",
+                        TAG, frame_index
+                    );
+                }
+            }
+        }
+
+        frames
+    }
+
+    fn frame_index(started: Instant, at: Instant) -> usize {
+        (at.saturating_duration_since(started).as_millis() / KEYFRAME_DURATION.as_millis()) as usize
+    }
+}
+
+/// Ramer-Douglas-Peucker on a single servo channel, marks the samples to keep.
+fn reduce(samples: &[u16], tolerance: u16, keep: &mut [bool]) {
+    let last = samples.len() - 1;
+    keep[0] = true;
+    keep[last] = true;
+
+    let mut spans = vec![(0, last)];
+
+    while let Some((start, end)) = spans.pop() {
+        if end - start < 2 {
+            continue;
+        }
+
+        let from = samples[start] as i32;
+        let to = samples[end] as i32;
+        let span = (end - start) as i32;
+
+        // Largest distance from the straight line between start and end
+        let mut worst_error = 0;
+        let mut worst_index = start;
+        for (index, sample) in samples.iter().enumerate().take(end).skip(start + 1) {
+            let offset = (index - start) as i32;
+            let expected = from * span + (to - from) * offset;
+            let error = (*sample as i32 * span - expected).abs() / span;
+
+            if error > worst_error {
+                worst_error = error;
+                worst_index = index;
+            }
+        }
+
+        if worst_error > tolerance as i32 {
+            keep[worst_index] = true;
+            spans.push((start, worst_index));
+            spans.push((worst_index, end));
+        }
+    }
+}
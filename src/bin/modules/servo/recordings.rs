@@ -0,0 +1,50 @@
+use alloc::{string::String, vec::Vec};
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+
+use super::animation::Frame;
+
+/// Recorded animations are kept in RAM, so they are lost on reboot.
+pub const MAX_RECORDINGS: usize = 8;
+
+struct Recording {
+    name: String,
+    frames: Vec<Option<Frame>>,
+}
+
+static RECORDINGS: Mutex<CriticalSectionRawMutex, RefCell<Vec<Recording>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+/// Save a recording, replacing one with the same name.
+///
+/// Returns the index of the recording, or `None` when all slots are taken.
+pub fn save(name: String, frames: Vec<Option<Frame>>) -> Option<usize> {
+    RECORDINGS.lock(|recordings| {
+        let mut recordings = recordings.borrow_mut();
+
+        if let Some(index) = recordings.iter().position(|r| r.name == name) {
+            recordings[index].frames = frames;
+            return Some(index);
+        }
+
+        if recordings.len() >= MAX_RECORDINGS {
+            return None;
+        }
+
+        recordings.push(Recording { name, frames });
+        Some(recordings.len() - 1)
+    })
+}
+
+pub fn find(name: &[u8]) -> Option<usize> {
+    RECORDINGS.lock(|recordings| {
+        recordings
+            .borrow()
+            .iter()
+            .position(|r| r.name.as_bytes() == name)
+    })
+}
+
+pub fn get(index: usize) -> Option<Vec<Option<Frame>>> {
+    RECORDINGS.lock(|recordings| recordings.borrow().get(index).map(|r| r.frames.clone()))
+}
@@ -0,0 +1,59 @@
+use alloc::vec::Vec;
+use esp_hal::rng::Rng;
+
+use owlimatronic_logic::servo::script::ScriptFrame;
+
+use super::{
+    animation::Frame,
+    config::{DEFAULT_NECK_POSITION, SERVO_COUNT, SERVO_MAX, SERVOS},
+};
+
+/// Composes animations out of smaller clips, see [`owlimatronic_logic::servo::script`].
+pub type Script = owlimatronic_logic::servo::script::Script<Frame>;
+
+/// Flatten the script into what the interpolator plays, with new random choices.
+pub fn compile(script: &Script) -> Vec<Option<Frame>> {
+    script.compile(&mut |count| Rng::new().random() as usize % count)
+}
+
+impl ScriptFrame for Frame {
+    fn mirror(&mut self) {
+        core::mem::swap(&mut self.wing_left_servo, &mut self.wing_right_servo);
+
+        if let Some((position, easing)) = self.neck_servo {
+            let mirrored = 2 * DEFAULT_NECK_POSITION as i32 - position as i32;
+            self.neck_servo = Some((mirrored.clamp(0, SERVO_MAX as i32) as u16, easing));
+        }
+    }
+
+    fn scale_amplitude(&mut self, percent: u16) {
+        for servo_index in 0..SERVO_COUNT {
+            if let Some((position, easing)) = self.get_servo(servo_index) {
+                let default = SERVOS[servo_index].default_position as i32;
+                let offset = (position as i32 - default) * percent as i32 / 100;
+                let scaled = (default + offset).clamp(0, SERVO_MAX as i32) as u16;
+                self.set_servo(servo_index, Some((scaled, easing)));
+            }
+        }
+    }
+
+    fn merge(&mut self, frame: &Frame) {
+        for servo_index in 0..SERVO_COUNT {
+            if let Some(keyframe) = frame.get_servo(servo_index) {
+                self.set_servo(servo_index, Some(keyframe));
+            }
+        }
+
+        if frame.eye_leds.is_some() {
+            self.eye_leds = frame.eye_leds;
+        }
+
+        if frame.chest_leds.is_some() {
+            self.chest_leds = frame.chest_leds;
+        }
+
+        if frame.audio.is_some() {
+            self.audio = frame.audio;
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use core::cell::RefCell;
+
+use defmt::{info, warn};
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    signal::Signal,
+};
+use embassy_time::{Duration, Timer};
+use embedded_storage::{ReadStorage, Storage};
+use esp_bootloader_esp_idf::partitions::{self, DataPartitionSubType, PartitionType};
+use esp_hal::peripherals::FLASH;
+use esp_storage::FlashStorage;
+
+use crate::modules::{audio::volume::Volume, motion::calibration::ImuCalibration};
+
+const TAG: &str = "[SETTINGS]";
+
+/// "OWLS", marks the start of a settings record.
+const MAGIC: u32 = 0x534C_574F;
+const HEADER_SIZE: usize = 10;
+const RECORD_SIZE: usize = 256;
+
+/// Wait for more changes before writing, flash wears out.
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+static SETTINGS: Mutex<CriticalSectionRawMutex, RefCell<Settings>> =
+    Mutex::new(RefCell::new(Settings::DEFAULT));
+static SAVE_TRIGGER: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Everything that survives a reboot.
+///
+/// Stored in the NVS partition. New fields go at the end of the record, so records written
+/// by older firmware still load with the new fields at their defaults.
+#[derive(Clone, Copy)]
+pub struct Settings {
+    pub imu_calibration: Option<ImuCalibration>,
+    pub volume: Volume,
+}
+
+impl Settings {
+    const DEFAULT: Settings = Settings {
+        imu_calibration: None,
+        volume: Volume::DEFAULT,
+    };
+
+    fn encode(&self, record: &mut [u8; RECORD_SIZE]) {
+        let mut writer = Writer {
+            buffer: &mut record[HEADER_SIZE..],
+            position: 0,
+        };
+
+        match &self.imu_calibration {
+            Some(calibration) => {
+                writer.put_u8(1);
+                writer.put_f32s(&calibration.accel_offset);
+                writer.put_f32s(&calibration.gyro_offset);
+                writer.put_f32s(&calibration.gravity);
+            }
+            None => writer.put_u8(0),
+        }
+
+        writer.put_u8(self.volume.master);
+        writer.put_u8(self.volume.track);
+        writer.put_u8(self.volume.stream);
+        writer.put_u8(self.volume.normalize_streams as u8);
+
+        let length = writer.position;
+        let checksum = checksum(&record[HEADER_SIZE..HEADER_SIZE + length]);
+        record[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        record[4..6].copy_from_slice(&(length as u16).to_le_bytes());
+        record[6..10].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    fn decode(record: &[u8; RECORD_SIZE]) -> Option<Settings> {
+        let magic = u32::from_le_bytes(record[0..4].try_into().ok()?);
+        let length = u16::from_le_bytes(record[4..6].try_into().ok()?) as usize;
+        let checksum_stored = u32::from_le_bytes(record[6..10].try_into().ok()?);
+
+        if magic != MAGIC || length > RECORD_SIZE - HEADER_SIZE {
+            return None;
+        }
+
+        let payload = &record[HEADER_SIZE..HEADER_SIZE + length];
+        if checksum(payload) != checksum_stored {
+            return None;
+        }
+
+        let mut reader = Reader {
+            buffer: payload,
+            position: 0,
+        };
+        let mut settings = Settings::DEFAULT;
+
+        if reader.get_u8() == Some(1) {
+            settings.imu_calibration = Some(ImuCalibration {
+                accel_offset: reader.get_f32s()?,
+                gyro_offset: reader.get_f32s()?,
+                gravity: reader.get_f32s()?,
+            });
+        }
+
+        // Missing in records written before the volume could be set
+        if let Some(master) = reader.get_u8() {
+            settings.volume = Volume {
+                master,
+                track: reader.get_u8()?,
+                stream: reader.get_u8()?,
+                normalize_streams: reader.get_u8()? == 1,
+            };
+        }
+
+        Some(settings)
+    }
+}
+
+pub fn get_settings() -> Settings {
+    SETTINGS.lock(|settings| *settings.borrow())
+}
+
+/// Change the settings, they are written to flash shortly after.
+pub fn update_settings(update: impl FnOnce(&mut Settings)) {
+    SETTINGS.lock(|settings| update(&mut settings.borrow_mut()));
+    SAVE_TRIGGER.signal(());
+}
+
+pub struct SettingsStorage {
+    flash: FlashStorage<'static>,
+    offset: u32,
+}
+
+impl SettingsStorage {
+    fn save(&mut self, settings: &Settings) {
+        let mut record = [0xFF; RECORD_SIZE];
+        settings.encode(&mut record);
+
+        match self.flash.write(self.offset, &record) {
+            Ok(()) => info!("{} Saved", TAG),
+            Err(_) => warn!("{} Failed to write flash", TAG),
+        }
+    }
+}
+
+/// Load the settings from flash, the defaults are kept when nothing valid is stored.
+///
+/// Returns `None` when there is no NVS partition to store settings in.
+pub fn settings_init(flash: FLASH<'static>) -> Option<SettingsStorage> {
+    let mut flash = FlashStorage::new(flash);
+
+    let mut table = [0u8; partitions::PARTITION_TABLE_MAX_LEN];
+    let partition = partitions::read_partition_table(&mut flash, &mut table)
+        .ok()
+        .and_then(|table| {
+            table
+                .find_partition(PartitionType::Data(DataPartitionSubType::Nvs))
+                .ok()
+                .flatten()
+        });
+
+    let Some(partition) = partition else {
+        warn!("{} No NVS partition, settings won't be saved", TAG);
+        return None;
+    };
+
+    let offset = partition.offset();
+    let mut record = [0u8; RECORD_SIZE];
+
+    match flash.read(offset, &mut record) {
+        Ok(()) => match Settings::decode(&record) {
+            Some(settings) => {
+                info!("{} Loaded", TAG);
+                SETTINGS.lock(|current| *current.borrow_mut() = settings);
+            }
+            None => info!("{} Nothing stored, using defaults", TAG),
+        },
+        Err(_) => warn!("{} Failed to read flash", TAG),
+    }
+
+    Some(SettingsStorage { flash, offset })
+}
+
+#[embassy_executor::task]
+pub async fn settings_task(mut storage: SettingsStorage) {
+    loop {
+        SAVE_TRIGGER.wait().await;
+        Timer::after(SAVE_DELAY).await;
+        SAVE_TRIGGER.reset();
+
+        storage.save(&get_settings());
+    }
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    // FNV-1a
+    data.iter().fold(0x811C_9DC5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+struct Writer<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl Writer<'_> {
+    fn put_u8(&mut self, value: u8) {
+        self.buffer[self.position] = value;
+        self.position += 1;
+    }
+
+    fn put_f32s(&mut self, values: &[f32]) {
+        for value in values {
+            self.buffer[self.position..self.position + 4].copy_from_slice(&value.to_le_bytes());
+            self.position += 4;
+        }
+    }
+}
+
+struct Reader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn get_u8(&mut self) -> Option<u8> {
+        let value = *self.buffer.get(self.position)?;
+        self.position += 1;
+        Some(value)
+    }
+
+    fn get_f32s<const N: usize>(&mut self) -> Option<[f32; N]> {
+        let mut values = [0.0; N];
+        for value in values.iter_mut() {
+            let bytes = self.buffer.get(self.position..self.position + 4)?;
+            *value = f32::from_le_bytes(bytes.try_into().ok()?);
+            self.position += 4;
+        }
+        Some(values)
+    }
+}
@@ -0,0 +1,135 @@
+use alloc::string::String;
+use core::cell::Cell;
+use core::fmt::Write;
+
+use defmt::{info, warn};
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    signal::Signal,
+};
+use rgb::RGB8;
+
+use crate::modules::{
+    connectivity::mqtt::{STATUS_TOPIC, publish},
+    indicator::{Priority, clear_effect, effects::Effect, set_effect},
+    mode::get_mode,
+};
+
+const TAG: &str = "[STATUS]";
+
+/// Blinked on the indicator while something isn't working.
+const ERROR_COLOR: RGB8 = RGB8::new(255, 80, 0);
+
+const COMPONENT_COUNT: usize = 1;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Component {
+    Motion = 0,
+}
+
+impl Component {
+    const ALL: [Component; COMPONENT_COUNT] = [Component::Motion];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Component::Motion => "motion",
+        }
+    }
+
+    /// Number of blinks on the indicator when the component isn't working.
+    pub fn get_blink_code(&self) -> u8 {
+        *self as u8 + 1
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Health {
+    Ok,
+    /// Not working right now, but recovering.
+    Degraded,
+    /// Gave up, the rest of the owl keeps working without it.
+    Failed,
+}
+
+impl Health {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Health::Ok => "ok",
+            Health::Degraded => "degraded",
+            Health::Failed => "failed",
+        }
+    }
+}
+
+static HEALTH: Mutex<CriticalSectionRawMutex, Cell<[Health; COMPONENT_COUNT]>> =
+    Mutex::new(Cell::new([Health::Ok; COMPONENT_COUNT]));
+static STATUS_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+pub fn report_health(component: Component, health: Health) {
+    let changed = HEALTH.lock(|cell| {
+        let mut components = cell.get();
+        let changed = components[component as usize] != health;
+        components[component as usize] = health;
+        cell.set(components);
+        changed
+    });
+
+    if changed {
+        STATUS_CHANGED.signal(());
+    }
+}
+
+fn find_unhealthy() -> Option<Component> {
+    let health = HEALTH.lock(|cell| cell.get());
+    Component::ALL
+        .into_iter()
+        .find(|component| health[*component as usize] != Health::Ok)
+}
+
+/// Mirrors the health of every component on the indicator and over MQTT.
+#[embassy_executor::task]
+pub async fn status_task() {
+    loop {
+        STATUS_CHANGED.wait().await;
+
+        match find_unhealthy() {
+            Some(component) => {
+                warn!("{} Running degraded, {} not ok", TAG, component.get_name());
+                let code = component.get_blink_code();
+                set_effect(
+                    Priority::Status,
+                    Effect::BlinkCode {
+                        color: ERROR_COLOR,
+                        code,
+                    },
+                );
+            }
+            None => {
+                info!("{} All components ok", TAG);
+                clear_effect(Priority::Status);
+            }
+        }
+
+        // Other modes would only fill the outbox, the status is sent again on connect
+        if get_mode().is_networked() {
+            publish_status();
+        }
+    }
+}
+
+/// Publish the health of every component, retained for whoever subscribes later.
+pub fn publish_status() {
+    // {"motion":"ok"}
+    let health = HEALTH.lock(|cell| cell.get());
+    let mut payload = String::from("{");
+    for (index, component) in Component::ALL.iter().enumerate() {
+        if index > 0 {
+            payload.push(',');
+        }
+        let state = health[*component as usize].get_name();
+        write!(payload, "\"{}\":\"{}\"", component.get_name(), state).ok();
+    }
+    payload.push('}');
+
+    publish(STATUS_TOPIC, payload, true);
+}
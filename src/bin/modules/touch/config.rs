@@ -0,0 +1,11 @@
+use embassy_time::Duration;
+
+/// Time between two readings of the pads.
+pub const SAMPLE_PERIOD: Duration = Duration::from_millis(20);
+
+/// Charge and discharge cycles counted by one measurement, more is slower but less noisy.
+pub const MEASURE_CYCLES: u16 = 500;
+/// Slow clock cycles between two scans of the pads.
+pub const SLEEP_CYCLES: u16 = 15;
+/// Charging current of the pads, from 0 to 7.
+pub const PAD_SLOPE: u8 = 7;
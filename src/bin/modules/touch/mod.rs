@@ -0,0 +1,51 @@
+use defmt::info;
+use embassy_futures::select::{Either, select};
+use embassy_time::{Instant, Ticker};
+use esp_hal::{gpio::AnyPin, peripherals::SENS};
+
+use crate::modules::events::{Event, publish_event};
+use crate::modules::power::{POWER_STATE, PowerState, report_activity};
+
+use config::SAMPLE_PERIOD;
+use detector::{PAD_COUNT, TouchDetector};
+use sensor::TouchSensor;
+
+pub mod config;
+pub mod sensor;
+
+pub use owlimatronic_logic::touch::detector;
+
+const TAG: &str = "[TOUCH]";
+
+/// Reads the pads in the order of [`detector::TouchPad::ALL`].
+#[embassy_executor::task]
+pub async fn touch_task(sens: SENS<'static>, pins: [AnyPin<'static>; PAD_COUNT]) {
+    info!("{} task started", TAG);
+    let sensor = TouchSensor::new(sens, pins);
+    let mut detector = TouchDetector::new();
+    let mut power_state = POWER_STATE.receiver().unwrap();
+    let mut ticker = Ticker::every(SAMPLE_PERIOD);
+
+    loop {
+        if let Either::Second(PowerState::Sleeping) =
+            select(ticker.next(), power_state.changed()).await
+        {
+            power_state
+                .changed_and(|state| *state == PowerState::Awake)
+                .await;
+            // The pads may have drifted a lot while sleeping
+            detector = TouchDetector::new();
+            continue;
+        }
+
+        let now = Instant::now().as_millis();
+        for event in detector.update(now, &sensor.read()) {
+            match event.get_pad() {
+                Some(pad) => info!("{} {}: {}", TAG, event.get_name(), pad.get_name()),
+                None => info!("{} {}", TAG, event.get_name()),
+            }
+            report_activity();
+            publish_event(Event::Touched(event));
+        }
+    }
+}
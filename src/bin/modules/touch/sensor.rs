@@ -0,0 +1,96 @@
+use esp_hal::{
+    gpio::{AnyPin, Pin},
+    peripherals::{LPWR, RTC_IO, SENS},
+};
+
+use super::{
+    config::{MEASURE_CYCLES, PAD_SLOPE, SLEEP_CYCLES},
+    detector::PAD_COUNT,
+};
+
+/// GPIOs 1 to 14 are touch channels 1 to 14.
+const TOUCH_CHANNELS: core::ops::RangeInclusive<u8> = 1..=14;
+
+/// Touch pads of the ESP32-S3, read through the registers until esp-hal has a driver for
+/// them, https://github.com/esp-rs/esp-hal/issues/1905
+///
+/// The scanning state machine measures the pads on its own, reading only picks up the last
+/// measurement.
+pub struct TouchSensor {
+    _sens: SENS<'static>,
+    _pins: [AnyPin<'static>; PAD_COUNT],
+    channels: [u8; PAD_COUNT],
+}
+
+impl TouchSensor {
+    pub fn new(sens: SENS<'static>, pins: [AnyPin<'static>; PAD_COUNT]) -> Self {
+        let channels = pins.each_ref().map(|pin| pin.number());
+        for channel in channels {
+            assert!(TOUCH_CHANNELS.contains(&channel), "not a touch pin");
+        }
+
+        let rtc_io = RTC_IO::regs();
+        for channel in channels {
+            // Hand the pad over to the RTC domain, without pulls and with a steady charge
+            rtc_io.touch_pad(channel as usize).modify(|_, w| unsafe {
+                w.mux_sel().set_bit();
+                w.fun_sel().bits(0);
+                w.fun_ie().clear_bit();
+                w.rue().clear_bit();
+                w.rde().clear_bit();
+                w.tie_opt().clear_bit();
+                w.dac().bits(PAD_SLOPE);
+                w.xpd().set_bit()
+            });
+        }
+
+        let rtc = LPWR::regs();
+        rtc.touch_ctrl1().write(|w| unsafe {
+            w.touch_meas_num().bits(MEASURE_CYCLES);
+            w.touch_sleep_cycles().bits(SLEEP_CYCLES)
+        });
+
+        // Pads that aren't scanned are grounded, so they don't couple into the others
+        let mask = channels.iter().fold(0, |mask, channel| mask | 1 << channel);
+        rtc.touch_scan_ctrl().modify(|r, w| unsafe {
+            w.touch_scan_pad_map()
+                .bits(r.touch_scan_pad_map().bits() | mask);
+            w.touch_inactive_connection().set_bit()
+        });
+
+        SENS::regs().sar_touch_conf().modify(|r, w| unsafe {
+            w.touch_outen().bits(r.touch_outen().bits() | mask);
+            // Raw readings, the baseline is tracked in software
+            w.touch_data_sel().bits(0)
+        });
+
+        // Charge between 0.5V and 2.7V, scanning on the sleep timer
+        rtc.touch_ctrl2().modify(|_, w| unsafe {
+            w.touch_drefh().bits(3);
+            w.touch_drefl().bits(0);
+            w.touch_drange().bits(3);
+            w.touch_xpd_bias().set_bit();
+            w.touch_clkgate_en().set_bit();
+            w.touch_start_force().clear_bit();
+            w.touch_slp_timer_en().set_bit();
+            w.touch_start_fsm_en().set_bit()
+        });
+
+        Self {
+            _sens: sens,
+            _pins: pins,
+            channels,
+        }
+    }
+
+    /// Last measurement of every pad, higher when touched.
+    pub fn read(&self) -> [u32; PAD_COUNT] {
+        let sens = SENS::regs();
+        self.channels.map(|channel| {
+            sens.sar_touch_status(channel as usize - 1)
+                .read()
+                .touch_pad_data()
+                .bits()
+        })
+    }
+}
@@ -0,0 +1,130 @@
+use core::cell::Cell;
+use core::future::Future;
+
+use defmt::{error, info};
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::{
+    peripherals::TIMG1,
+    system::software_reset,
+    timer::timg::{MwdtStage, Wdt},
+};
+
+use crate::modules::crash::record_watchdog;
+
+const TAG: &str = "[WATCHDOG]";
+
+/// How often the supervisor checks the tasks and feeds the hardware watchdog.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Resets the chip when the supervisor itself stops running, e.g. a task that never yields.
+const HARDWARE_TIMEOUT: esp_hal::time::Duration = esp_hal::time::Duration::from_secs(5);
+
+const TASK_COUNT: usize = 3;
+
+/// Tasks that have to check in while they are busy.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SupervisedTask {
+    Servo = 0,
+    Audio = 1,
+    Mqtt = 2,
+}
+
+impl SupervisedTask {
+    const ALL: [SupervisedTask; TASK_COUNT] = [
+        SupervisedTask::Servo,
+        SupervisedTask::Audio,
+        SupervisedTask::Mqtt,
+    ];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            SupervisedTask::Servo => "servo",
+            SupervisedTask::Audio => "audio",
+            SupervisedTask::Mqtt => "mqtt",
+        }
+    }
+
+    /// Longest time the task may go without checking in while busy.
+    fn get_timeout(&self) -> Duration {
+        match self {
+            SupervisedTask::Servo => Duration::from_secs(5),
+            SupervisedTask::Audio => Duration::from_secs(5),
+            // Pings the broker every 30 seconds
+            SupervisedTask::Mqtt => Duration::from_secs(90),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum TaskState {
+    /// Not started, or waiting for work.
+    Idle,
+    Busy(Instant),
+}
+
+static TASKS: Mutex<CriticalSectionRawMutex, Cell<[TaskState; TASK_COUNT]>> =
+    Mutex::new(Cell::new([TaskState::Idle; TASK_COUNT]));
+
+fn set_state(task: SupervisedTask, state: TaskState) {
+    TASKS.lock(|tasks| {
+        let mut states = tasks.get();
+        states[task as usize] = state;
+        tasks.set(states);
+    });
+}
+
+/// Tell the supervisor the task is still making progress.
+pub fn check_in(task: SupervisedTask) {
+    set_state(task, TaskState::Busy(Instant::now()));
+}
+
+/// Wait for work without being supervised, the task is busy again once it arrives.
+pub async fn idle<F: Future>(task: SupervisedTask, future: F) -> F::Output {
+    set_state(task, TaskState::Idle);
+    let output = future.await;
+    check_in(task);
+    output
+}
+
+/// Whether no task is busy, e.g. before something blocks all of them.
+pub fn is_all_idle() -> bool {
+    let states = TASKS.lock(|tasks| tasks.get());
+    states.iter().all(|state| matches!(state, TaskState::Idle))
+}
+
+fn find_starved_task() -> Option<(SupervisedTask, Duration)> {
+    let states = TASKS.lock(|tasks| tasks.get());
+    let now = Instant::now();
+
+    SupervisedTask::ALL.into_iter().find_map(|task| {
+        let TaskState::Busy(last_check_in) = states[task as usize] else {
+            return None;
+        };
+        let starved_for = now.saturating_duration_since(last_check_in);
+        (starved_for > task.get_timeout()).then_some((task, starved_for))
+    })
+}
+
+/// Feed the hardware watchdog as long as every busy task keeps checking in.
+#[embassy_executor::task]
+pub async fn watchdog_task(mut wdt: Wdt<TIMG1<'static>>) {
+    wdt.set_timeout(MwdtStage::Stage0, HARDWARE_TIMEOUT);
+    wdt.enable();
+    info!("{} task started", TAG);
+
+    loop {
+        if let Some((task, starved_for)) = find_starved_task() {
+            error!(
+                "{} {} task starved for {}ms, resetting",
+                TAG,
+                task.get_name(),
+                starved_for.as_millis()
+            );
+            record_watchdog(task.get_name(), starved_for);
+            software_reset();
+        }
+
+        wdt.feed();
+        Timer::after(CHECK_INTERVAL).await;
+    }
+}
@@ -0,0 +1,65 @@
+// Records the IMU debug stream of the owl to a CSV file.
+//
+// Usage: node scripts/imu-record.js [file.csv] [rate]
+//
+// Starts streaming at `rate` samples per second over MQTT when MQTT_CONNECTION_URL is set,
+// otherwise publish the rate to owlimatronic/motion/debug yourself. Stop with Ctrl+C.
+// Record at 200 to get every sample, traces replayed through the gesture recognizer in logic/
+// need them all.
+import dgram from "node:dgram";
+import fs from "node:fs";
+import { connect } from "mqtt";
+
+const TAG = "[IMU]";
+const PORT = Number(process.env.TELEMETRY_PORT ?? 9001);
+const DEBUG_TOPIC = "owlimatronic/motion/debug";
+
+const file = process.argv[2] ?? `imu-${new Date().toISOString().replace(/[:.]/g, "-")}.csv`;
+const rate = Number(process.argv[3] ?? 50);
+
+const output = fs.createWriteStream(file);
+
+let samples = 0;
+let header = false;
+const socket = dgram.createSocket("udp4");
+
+socket.on("message", message => {
+  for (const line of message.toString().split("\n")) {
+    if (!line) continue;
+
+    // The owl sends the column names whenever streaming starts, keep them once
+    const columns = line.split(",");
+    if (Number.isNaN(Number(columns[0]))) {
+      if (!header) output.write(`${line}\n`);
+      header = true;
+      continue;
+    }
+
+    output.write(`${line}\n`);
+    samples++;
+    if (columns[11]) console.log(`${TAG} ${columns[11]}`);
+  }
+});
+
+socket.bind(PORT, () => console.log(`${TAG} Recording to ${file} on port ${PORT}`));
+
+const mqtt = process.env.MQTT_CONNECTION_URL
+  ? connect(process.env.MQTT_CONNECTION_URL, {
+      username: process.env.MQTT_USERNAME,
+      password: process.env.MQTT_PASSWORD,
+    })
+  : null;
+
+mqtt?.on("connect", () => {
+  console.log(`${TAG} Streaming at ${rate}Hz`);
+  mqtt.publish(DEBUG_TOPIC, String(rate));
+});
+
+process.on("SIGINT", () => {
+  console.log(`${TAG} Stopping, ${samples} samples recorded`);
+  socket.close();
+  output.end();
+
+  if (!mqtt) process.exit(0);
+  mqtt.publish(DEBUG_TOPIC, "0", () => mqtt.end(false, () => process.exit(0)));
+});
@@ -39,6 +39,14 @@ class MQTTClient {
         console.log(`${TAG} Publishing to ${topic}: ${message}`);
         this.client.publish(topic, message);
     }
+
+    // High rate binary messages, not logged
+    public publishBinary(topic: string, message: Buffer) {
+        if (this.client.disconnected) {
+            return "Client not connected";
+        }
+        this.client.publish(topic, message, { qos: 0 });
+    }
 }
 
 export const mqttClient = new MQTTClient();
\ No newline at end of file
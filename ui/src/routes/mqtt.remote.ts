@@ -1,5 +1,5 @@
 import * as v from 'valibot';
-import { form } from '$app/server';
+import { command, form } from '$app/server';
 import { mqttClient } from "$lib/server/services/mqtt";
 
 export const playAnimation = form(
@@ -11,4 +11,38 @@ export const playAnimation = form(
 
         return result;
     }
+);
+
+// Marks a servo as unchanged, must match `UNCHANGED` in the firmware
+const SERVO_UNCHANGED = 0xffff;
+const servoTarget = v.optional(v.pipe(v.number(), v.integer(), v.minValue(0), v.maxValue(1000)));
+
+export const setServos = command(
+    v.object({
+        beak: servoTarget,
+        neck: servoTarget,
+        wing_right: servoTarget,
+        wing_left: servoTarget,
+    }),
+    async ({ beak, neck, wing_right, wing_left }) => {
+        // One little-endian u16 per servo: beak, neck, right wing, left wing
+        const payload = Buffer.alloc(8);
+        [beak, neck, wing_right, wing_left].forEach((target, index) => {
+            payload.writeUInt16LE(target ?? SERVO_UNCHANGED, index * 2);
+        });
+
+        return mqttClient.publishBinary("owlimatronic/servo/set", payload);
+    }
+);
+
+export const recordAnimation = command(
+    v.object({
+        // Name to save the recording under, omit to start recording
+        name: v.optional(v.pipe(v.string(), v.nonEmpty())),
+    }),
+    async ({ name }) => {
+        const payload = name === undefined ? "start" : `stop:${name}`;
+
+        return mqttClient.publish("owlimatronic/servo/record", payload);
+    }
 );
\ No newline at end of file